@@ -0,0 +1,473 @@
+//! Audit trail of the writes made to Salesforce through the service, recording who changed which
+//! record, what they sent and whether it succeeded. Entries are written to a configurable sink,
+//! e.g. `"Audit": { "Sink": "File", "Path": "/var/log/salesforce-api/audit.jsonl" }`.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::error;
+
+use crate::authentication::Caller;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::organization::SalesforceOrganization;
+use crate::policy::PolicyOperation;
+use crate::redaction::redactor;
+use crate::request_context::current_request_id;
+use crate::salesforce::changes::FieldChange;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "Sink")]
+pub enum AuditConfiguration {
+    /// Writes entries as JSON lines to standard output, alongside the service logs.
+    #[default]
+    Stdout,
+    /// Appends entries as JSON lines to a file.
+    File {
+        #[serde(rename = "Path")]
+        path: String,
+    },
+    /// Inserts entries into the `audit_log` table of a SQLite database, creating it if necessary.
+    Sqlite {
+        #[serde(rename = "Path")]
+        path: String,
+    },
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOperation {
+    Create,
+    Update,
+    Upsert,
+    Delete,
+}
+
+impl AuditOperation {
+    /// The audited operation a policy operation writes with, or `None` for reads.
+    pub fn for_policy_operation(operation: PolicyOperation) -> Option<Self> {
+        match operation {
+            PolicyOperation::Create => Some(Self::Create),
+            PolicyOperation::Update => Some(Self::Update),
+            PolicyOperation::Delete => Some(Self::Delete),
+            PolicyOperation::Read | PolicyOperation::Query => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Upsert => "upsert",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOutcome {
+    Pending,
+    Succeeded,
+    Failed,
+    /// The caller wasn't permitted to make the write, so it was never sent to Salesforce.
+    Denied,
+}
+
+impl AuditOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Denied => "denied",
+        }
+    }
+}
+
+/// A single write attempted on behalf of a caller. Values in `changes` are redacted the same way
+/// they are in logs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub request_id: Option<String>,
+    pub caller: String,
+    pub organization: SalesforceOrganization,
+    pub operation: AuditOperation,
+    pub object: String,
+    pub record_id: Option<String>,
+    pub changes: Value,
+    /// Previous and new values of the fields an update changed, when they were captured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<Value>,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        caller: &Caller,
+        organization: SalesforceOrganization,
+        operation: AuditOperation,
+        object: &str,
+        record_id: Option<&str>,
+        changes: &Value,
+    ) -> Self {
+        Self {
+            timestamp: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            request_id: current_request_id(),
+            caller: caller.id.clone(),
+            organization,
+            operation,
+            object: object.to_string(),
+            record_id: record_id.map(str::to_string),
+            changes: redactor().redact_value(changes),
+            diff: None,
+            outcome: AuditOutcome::Pending,
+            error: None,
+        }
+    }
+
+    /// Records the field level changes the write made, keyed by field name.
+    pub fn with_diff(mut self, diff: &[FieldChange]) -> Self {
+        let diff = diff
+            .iter()
+            .map(|change| {
+                let values = serde_json::json!({
+                    "oldValue": change.old_value,
+                    "newValue": change.new_value,
+                });
+                (change.field.clone(), values)
+            })
+            .collect();
+        self.diff = Some(redactor().redact_value(&Value::Object(diff)));
+        self
+    }
+
+    /// Records how the write turned out.
+    pub fn with_result<T>(mut self, result: &ServiceResult<T>) -> Self {
+        match result {
+            Ok(_) => self.outcome = AuditOutcome::Succeeded,
+            Err(e) => {
+                self.outcome = match e {
+                    ServiceError::Forbidden(_) => AuditOutcome::Denied,
+                    _ => AuditOutcome::Failed,
+                };
+                self.error = Some(redactor().redact_text(&e.to_string()));
+            }
+        }
+        self
+    }
+}
+
+/// A destination for audit entries.
+pub trait AuditSink: fmt::Debug + Send + Sync {
+    fn write(&self, entry: &AuditEntry) -> ServiceResult<()>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StdoutAuditSink;
+
+impl AuditSink for StdoutAuditSink {
+    fn write(&self, entry: &AuditEntry) -> ServiceResult<()> {
+        let line = serde_json::to_string(entry)?;
+        writeln!(io::stdout().lock(), "{line}").map_err(audit_failed)
+    }
+}
+
+#[derive(Debug)]
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    pub fn open(path: &str) -> ServiceResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(audit_failed)?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn write(&self, entry: &AuditEntry) -> ServiceResult<()> {
+        let line = serde_json::to_string(entry)?;
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{line}")
+            .and_then(|_| file.flush())
+            .map_err(audit_failed)
+    }
+}
+
+pub struct SqliteAuditSink {
+    connection: Mutex<Connection>,
+}
+
+impl fmt::Debug for SqliteAuditSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteAuditSink").finish_non_exhaustive()
+    }
+}
+
+impl SqliteAuditSink {
+    pub fn open(path: &str) -> ServiceResult<Self> {
+        let connection = Connection::open(path).map_err(audit_failed)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS audit_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp TEXT NOT NULL,
+                    request_id TEXT,
+                    caller TEXT NOT NULL,
+                    organization TEXT NOT NULL,
+                    operation TEXT NOT NULL,
+                    object TEXT NOT NULL,
+                    record_id TEXT,
+                    changes TEXT NOT NULL,
+                    diff TEXT,
+                    outcome TEXT NOT NULL,
+                    error TEXT
+                )",
+                (),
+            )
+            .map_err(audit_failed)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl AuditSink for SqliteAuditSink {
+    fn write(&self, entry: &AuditEntry) -> ServiceResult<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO audit_log (timestamp, request_id, caller, organization, operation,
+                    object, record_id, changes, diff, outcome, error)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    entry.timestamp,
+                    entry.request_id,
+                    entry.caller,
+                    entry.organization.as_str(),
+                    entry.operation.as_str(),
+                    entry.object,
+                    entry.record_id,
+                    entry.changes.to_string(),
+                    entry.diff.as_ref().map(Value::to_string),
+                    entry.outcome.as_str(),
+                    entry.error,
+                ],
+            )
+            .map(|_| ())
+            .map_err(audit_failed)
+    }
+}
+
+fn audit_failed(e: impl fmt::Display) -> ServiceError {
+    ServiceError::AuditLogFailed(e.to_string())
+}
+
+#[derive(Debug)]
+pub struct AuditLog {
+    sink: Option<Arc<dyn AuditSink>>,
+}
+
+impl AuditLog {
+    pub fn new(config: &AuditConfiguration) -> ServiceResult<Self> {
+        let audit_log = match config {
+            AuditConfiguration::Stdout => Self::with_sink(StdoutAuditSink),
+            AuditConfiguration::File { path } => Self::with_sink(FileAuditSink::open(path)?),
+            AuditConfiguration::Sqlite { path } => Self::with_sink(SqliteAuditSink::open(path)?),
+            AuditConfiguration::Disabled => Self { sink: None },
+        };
+
+        Ok(audit_log)
+    }
+
+    pub fn with_sink(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Some(Arc::new(sink)),
+        }
+    }
+
+    /// Writes the entry to the sink. Sinks write to files and databases, so this happens on the
+    /// blocking thread pool rather than holding up the runtime. The write the entry describes has
+    /// already happened by now, so a failure is logged rather than failing the request.
+    pub async fn record(&self, entry: AuditEntry) {
+        let Some(sink) = self.sink.clone() else {
+            return;
+        };
+
+        let result = tokio::task::spawn_blocking(move || {
+            let result = sink.write(&entry);
+            (entry, result)
+        })
+        .await;
+
+        match result {
+            Ok((_, Ok(()))) => {}
+            Ok((entry, Err(e))) => error!(
+                "Failed to audit {} of {} {:?} by {}: {e}",
+                entry.operation.as_str(),
+                entry.object,
+                entry.record_id,
+                entry.caller
+            ),
+            Err(e) => error!("Audit sink write did not complete: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::authentication::AuthenticationMethod;
+    use crate::errors::SalesforceApiError;
+
+    /// Keeps entries in memory so tests can inspect what was recorded.
+    #[derive(Debug, Clone, Default)]
+    struct MemoryAuditSink(Arc<Mutex<Vec<AuditEntry>>>);
+
+    impl AuditSink for MemoryAuditSink {
+        fn write(&self, entry: &AuditEntry) -> ServiceResult<()> {
+            self.0.lock().unwrap().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    fn entry() -> AuditEntry {
+        let caller = Caller {
+            id: "underwriting-ui".to_string(),
+            method: AuthenticationMethod::ApiKey,
+            organizations: None,
+        };
+
+        AuditEntry::new(
+            &caller,
+            SalesforceOrganization::Underwriting,
+            AuditOperation::Update,
+            "Account",
+            Some("001000000000001AAA"),
+            &json!({ "Name": "Acme", "SSN__c": "123-45-6789", "Description": "EIN 12-3456789" }),
+        )
+    }
+
+    #[tokio::test]
+    async fn entries_are_recorded_with_their_outcome() {
+        let sink = MemoryAuditSink::default();
+        let audit = AuditLog::with_sink(sink.clone());
+
+        audit
+            .record(entry().with_result::<()>(&Err(ServiceError::PreconditionFailed)))
+            .await;
+        audit
+            .record(entry().with_result::<()>(&Err(ServiceError::Forbidden(
+                "Writing SSN__c is not permitted.".to_string(),
+            ))))
+            .await;
+        audit.record(entry().with_result(&Ok(()))).await;
+
+        let entries = sink.0.lock().unwrap();
+        let outcomes: Vec<_> = entries.iter().map(|entry| entry.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                AuditOutcome::Failed,
+                AuditOutcome::Denied,
+                AuditOutcome::Succeeded
+            ]
+        );
+        assert_eq!(
+            entries[0].error.as_deref(),
+            Some(ServiceError::PreconditionFailed.to_string().as_str())
+        );
+        assert_eq!(entries[2].error, None);
+    }
+
+    #[tokio::test]
+    async fn sqlite_entries_are_redacted() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("audit.db");
+        let path = path.to_str().unwrap();
+
+        let audit = AuditLog::new(&AuditConfiguration::Sqlite {
+            path: path.to_string(),
+        })
+        .unwrap();
+        let diff = [FieldChange {
+            field: "SSN__c".to_string(),
+            old_value: json!("987-65-4321"),
+            new_value: json!("123-45-6789"),
+        }];
+        audit
+            .record(entry().with_diff(&diff).with_result::<()>(&Err(
+                ServiceError::SalesforceRequestFailed(
+                    400,
+                    vec![SalesforceApiError {
+                        error_code: "FIELD_CUSTOM_VALIDATION_EXCEPTION".to_string(),
+                        message: "SSN 123-45-6789 is already in use".to_string(),
+                        fields: vec!["SSN__c".to_string()],
+                    }],
+                ),
+            )))
+            .await;
+
+        let connection = Connection::open(path).unwrap();
+        let (caller, record_id, changes, diff, outcome, error): (
+            String,
+            String,
+            String,
+            String,
+            String,
+            String,
+        ) = connection
+            .query_row(
+                "SELECT caller, record_id, changes, diff, outcome, error FROM audit_log",
+                (),
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+
+        assert_eq!(caller, "underwriting-ui");
+        assert_eq!(record_id, "001000000000001AAA");
+        assert_eq!(
+            serde_json::from_str::<Value>(&changes).unwrap(),
+            json!({ "Name": "Acme", "SSN__c": "***", "Description": "EIN ***" })
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&diff).unwrap(),
+            json!({ "SSN__c": "***" })
+        );
+        assert_eq!(outcome, "failed");
+        assert_eq!(error, "SSN *** is already in use");
+    }
+}
@@ -0,0 +1,599 @@
+//! Authentication of the callers using the API. Each configured [`Authenticator`] inspects the
+//! request headers for its own kind of credential, and the first to recognize one decides who the
+//! caller is. Requests nobody recognizes are turned away before reaching a handler.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+use crate::errors::{ServiceError, ServiceResult};
+use crate::organization::SalesforceOrganization;
+use crate::redaction::REDACTED;
+use crate::router::RouterState;
+
+/// Header callers send their API key in.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+const DEFAULT_CALLER_CLAIM: &str = "sub";
+
+const DEFAULT_ORGANIZATIONS_CLAIM: &str = "organizations";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct AuthenticationConfiguration {
+    pub api_keys: Vec<ApiKeyConfiguration>,
+    pub jwt: Option<JwtConfiguration>,
+    /// Lets unauthenticated requests through when no credentials are configured, for local
+    /// development only.
+    pub allow_anonymous: bool,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiKeyConfiguration {
+    /// Identity of the caller the key was issued to.
+    pub caller: String,
+    pub key: String,
+    /// Organizations the caller may use, or all of them when omitted.
+    pub organizations: Option<Vec<SalesforceOrganization>>,
+}
+
+impl fmt::Debug for ApiKeyConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyConfiguration")
+            .field("caller", &self.caller)
+            .field("key", &REDACTED)
+            .field("organizations", &self.organizations)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JwtConfiguration {
+    /// Path to a JSON Web Key Set file holding the keys tokens are signed with.
+    pub jwks_path: String,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Claim identifying the caller, `sub` by default.
+    pub caller_claim: Option<String>,
+    /// Claim listing the organizations the caller may use, `organizations` by default. Callers
+    /// whose tokens don't carry the claim may use all of them.
+    pub organizations_claim: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthenticationMethod {
+    ApiKey,
+    Jwt,
+    Anonymous,
+}
+
+/// The authenticated identity making a request.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Caller {
+    pub id: String,
+    pub method: AuthenticationMethod,
+    pub organizations: Option<HashSet<SalesforceOrganization>>,
+}
+
+impl Caller {
+    fn anonymous() -> Self {
+        Self {
+            id: "anonymous".to_string(),
+            method: AuthenticationMethod::Anonymous,
+            organizations: None,
+        }
+    }
+
+    /// Ensures the caller has been granted access to the organization.
+    pub fn authorize_organization(
+        &self,
+        organization: SalesforceOrganization,
+    ) -> ServiceResult<()> {
+        match &self.organizations {
+            Some(organizations) if !organizations.contains(&organization) => {
+                Err(ServiceError::Forbidden(format!(
+                    "{} is not permitted to access {organization}.",
+                    self.id
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What an authenticator made of a request's credentials.
+#[derive(Debug)]
+pub enum AuthenticationOutcome {
+    /// The request carries no credentials of the kind this authenticator handles.
+    NotPresent,
+    Authenticated(Caller),
+    /// Credentials were presented but are not valid.
+    Rejected(String),
+}
+
+/// A source of caller identities, such as API keys or bearer tokens.
+pub trait Authenticator: fmt::Debug + Send + Sync {
+    fn authenticate(&self, headers: &HeaderMap) -> AuthenticationOutcome;
+}
+
+#[derive(Debug)]
+pub struct ApiKeyAuthenticator {
+    keys: Vec<ApiKeyConfiguration>,
+}
+
+impl ApiKeyAuthenticator {
+    pub fn new(keys: Vec<ApiKeyConfiguration>) -> Self {
+        Self { keys }
+    }
+}
+
+impl Authenticator for ApiKeyAuthenticator {
+    fn authenticate(&self, headers: &HeaderMap) -> AuthenticationOutcome {
+        let Some(presented) = headers.get(API_KEY_HEADER) else {
+            return AuthenticationOutcome::NotPresent;
+        };
+
+        let matched = self
+            .keys
+            .iter()
+            .find(|key| constant_time_eq(key.key.as_bytes(), presented.as_bytes()));
+
+        match matched {
+            Some(key) => AuthenticationOutcome::Authenticated(Caller {
+                id: key.caller.clone(),
+                method: AuthenticationMethod::ApiKey,
+                organizations: key
+                    .organizations
+                    .as_ref()
+                    .map(|organizations| organizations.iter().copied().collect()),
+            }),
+            None => AuthenticationOutcome::Rejected("The API key is not valid.".to_string()),
+        }
+    }
+}
+
+/// Compares keys without exiting early, so response timing doesn't reveal how much of a guess
+/// was correct.
+fn constant_time_eq(expected: &[u8], presented: &[u8]) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .iter()
+            .zip(presented)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Validates bearer tokens against the keys in a local JWKS file, loaded once at startup.
+#[derive(Debug)]
+pub struct JwtAuthenticator {
+    jwks: JwkSet,
+    config: JwtConfiguration,
+}
+
+impl JwtAuthenticator {
+    pub fn new(config: JwtConfiguration) -> ServiceResult<Self> {
+        let contents = fs::read_to_string(&config.jwks_path).map_err(|e| {
+            ServiceError::AuthenticationConfigurationInvalid(format!(
+                "JWKS file {} could not be read: {e}",
+                config.jwks_path
+            ))
+        })?;
+        let jwks = serde_json::from_str::<JwkSet>(&contents).map_err(|e| {
+            ServiceError::AuthenticationConfigurationInvalid(format!(
+                "JWKS file {} is not valid: {e}",
+                config.jwks_path
+            ))
+        })?;
+
+        info!(
+            "Loaded {} signing keys from {}",
+            jwks.keys.len(),
+            config.jwks_path
+        );
+
+        Ok(Self { jwks, config })
+    }
+
+    fn validate(&self, token: &str) -> Result<Caller, String> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| format!("The bearer token is malformed: {e}"))?;
+
+        let jwk = match &header.kid {
+            Some(kid) => self.jwks.find(kid),
+            None if self.jwks.keys.len() == 1 => self.jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| "The bearer token was not signed by a trusted key.".to_string())?;
+
+        // Keys that declare their algorithm must only be used with it
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            if Algorithm::from_str(&key_algorithm.to_string()) != Ok(header.alg) {
+                return Err("The bearer token was not signed by a trusted key.".to_string());
+            }
+        }
+
+        let key = DecodingKey::from_jwk(jwk)
+            .map_err(|e| format!("The signing key could not be used: {e}"))?;
+
+        let mut validation = Validation::new(header.alg);
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let claims = jsonwebtoken::decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(|e| format!("The bearer token is not valid: {e}"))?
+            .claims;
+
+        let caller_claim = self
+            .config
+            .caller_claim
+            .as_deref()
+            .unwrap_or(DEFAULT_CALLER_CLAIM);
+        let id = claims
+            .get(caller_claim)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("The bearer token has no {caller_claim} claim."))?
+            .to_string();
+
+        let organizations_claim = self
+            .config
+            .organizations_claim
+            .as_deref()
+            .unwrap_or(DEFAULT_ORGANIZATIONS_CLAIM);
+        let organizations = claims
+            .get(organizations_claim)
+            .and_then(Value::as_array)
+            .map(|organizations| {
+                organizations
+                    .iter()
+                    .filter_map(|organization| {
+                        serde_json::from_value::<SalesforceOrganization>(organization.clone()).ok()
+                    })
+                    .collect()
+            });
+
+        Ok(Caller {
+            id,
+            method: AuthenticationMethod::Jwt,
+            organizations,
+        })
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, headers: &HeaderMap) -> AuthenticationOutcome {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            None => AuthenticationOutcome::NotPresent,
+            Some(token) => match self.validate(token.trim()) {
+                Ok(caller) => AuthenticationOutcome::Authenticated(caller),
+                Err(reason) => AuthenticationOutcome::Rejected(reason),
+            },
+        }
+    }
+}
+
+/// The authenticators requests are checked against, in order.
+#[derive(Debug)]
+pub struct Authentication {
+    authenticators: Vec<Box<dyn Authenticator>>,
+    allow_anonymous: bool,
+}
+
+impl Authentication {
+    pub fn new(config: &AuthenticationConfiguration) -> ServiceResult<Self> {
+        let mut authentication = Self {
+            authenticators: Vec::new(),
+            allow_anonymous: config.allow_anonymous,
+        };
+
+        if !config.api_keys.is_empty() {
+            authentication = authentication
+                .with_authenticator(ApiKeyAuthenticator::new(config.api_keys.clone()));
+        }
+
+        if let Some(jwt) = &config.jwt {
+            authentication = authentication.with_authenticator(JwtAuthenticator::new(jwt.clone())?);
+        }
+
+        if authentication.authenticators.is_empty() && !authentication.allow_anonymous {
+            warn!("No caller credentials are configured, every request will be rejected");
+        }
+
+        Ok(authentication)
+    }
+
+    /// Adds another way for callers to authenticate, checked after those already added.
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Box::new(authenticator));
+        self
+    }
+
+    pub fn authenticate(&self, headers: &HeaderMap) -> ServiceResult<Caller> {
+        for authenticator in &self.authenticators {
+            match authenticator.authenticate(headers) {
+                AuthenticationOutcome::NotPresent => continue,
+                AuthenticationOutcome::Authenticated(caller) => return Ok(caller),
+                AuthenticationOutcome::Rejected(reason) => {
+                    return Err(ServiceError::Unauthenticated(reason))
+                }
+            }
+        }
+
+        if self.allow_anonymous {
+            return Ok(Caller::anonymous());
+        }
+
+        Err(ServiceError::Unauthenticated(
+            "Credentials are required to access this resource.".to_string(),
+        ))
+    }
+}
+
+/// Rejects requests without valid credentials, making the authenticated [`Caller`] available to
+/// handlers through the request extensions.
+pub async fn authenticate_requests(
+    State(state): State<Arc<RouterState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match state.authentication.authenticate(request.headers()) {
+        Ok(caller) => {
+            info!("Request authenticated as {}", caller.id);
+            request.extensions_mut().insert(caller);
+            next.run(request).await
+        }
+        Err(e) => {
+            warn!("Rejecting unauthenticated request: {e}");
+            e.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn jwks_file() -> NamedTempFile {
+        let jwks = json!({
+            "keys": [{
+                "kty": "oct",
+                "kid": "signing-key",
+                "alg": "HS256",
+                "k": "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY",
+            }]
+        });
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(jwks.to_string().as_bytes()).unwrap();
+        file
+    }
+
+    fn authenticator(jwks: &NamedTempFile) -> JwtAuthenticator {
+        JwtAuthenticator::new(JwtConfiguration {
+            jwks_path: jwks.path().to_string_lossy().to_string(),
+            issuer: Some("https://issuer.example.com".to_string()),
+            audience: Some("salesforce-api".to_string()),
+            caller_claim: None,
+            organizations_claim: None,
+        })
+        .unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "underwriting-ui",
+            "iss": "https://issuer.example.com",
+            "aud": "salesforce-api",
+            "exp": now() + 300,
+            "organizations": ["Underwriting"],
+        })
+    }
+
+    fn token(algorithm: Algorithm, kid: Option<&str>, claims: &Value) -> String {
+        let header = Header {
+            kid: kid.map(str::to_string),
+            ..Header::new(algorithm)
+        };
+        jsonwebtoken::encode(&header, claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    fn rejection(outcome: AuthenticationOutcome) -> String {
+        match outcome {
+            AuthenticationOutcome::Rejected(reason) => reason,
+            outcome => panic!("expected the credentials to be rejected, got {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_tokens_identify_the_caller_and_their_organizations() {
+        let jwks = jwks_file();
+        let token = token(Algorithm::HS256, Some("signing-key"), &claims());
+
+        match authenticator(&jwks).authenticate(&bearer(&token)) {
+            AuthenticationOutcome::Authenticated(caller) => {
+                assert_eq!(caller.id, "underwriting-ui");
+                assert_eq!(caller.method, AuthenticationMethod::Jwt);
+                assert!(caller
+                    .authorize_organization(SalesforceOrganization::Underwriting)
+                    .is_ok());
+                assert!(caller
+                    .authorize_organization(SalesforceOrganization::QuickBridge)
+                    .is_err());
+            }
+            outcome => panic!("expected the token to be accepted, got {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let jwks = jwks_file();
+        let mut claims = claims();
+        claims["exp"] = json!(now() - 600);
+        let token = token(Algorithm::HS256, Some("signing-key"), &claims);
+
+        let reason = rejection(authenticator(&jwks).authenticate(&bearer(&token)));
+
+        assert!(reason.contains("ExpiredSignature"), "{reason}");
+    }
+
+    #[test]
+    fn tokens_for_another_audience_are_rejected() {
+        let jwks = jwks_file();
+        let mut claims = claims();
+        claims["aud"] = json!("another-api");
+        let token = token(Algorithm::HS256, Some("signing-key"), &claims);
+
+        let reason = rejection(authenticator(&jwks).authenticate(&bearer(&token)));
+
+        assert!(reason.contains("InvalidAudience"), "{reason}");
+    }
+
+    #[test]
+    fn tokens_from_another_issuer_are_rejected() {
+        let jwks = jwks_file();
+        let mut claims = claims();
+        claims["iss"] = json!("https://attacker.example.com");
+        let token = token(Algorithm::HS256, Some("signing-key"), &claims);
+
+        let reason = rejection(authenticator(&jwks).authenticate(&bearer(&token)));
+
+        assert!(reason.contains("InvalidIssuer"), "{reason}");
+    }
+
+    #[test]
+    fn tokens_signed_with_an_algorithm_the_key_does_not_declare_are_rejected() {
+        let jwks = jwks_file();
+        let token = token(Algorithm::HS384, Some("signing-key"), &claims());
+
+        let reason = rejection(authenticator(&jwks).authenticate(&bearer(&token)));
+
+        assert_eq!(reason, "The bearer token was not signed by a trusted key.");
+    }
+
+    #[test]
+    fn tokens_with_an_unknown_key_id_are_rejected() {
+        let jwks = jwks_file();
+        let token = token(Algorithm::HS256, Some("rotated-out"), &claims());
+
+        let reason = rejection(authenticator(&jwks).authenticate(&bearer(&token)));
+
+        assert_eq!(reason, "The bearer token was not signed by a trusted key.");
+    }
+
+    #[test]
+    fn requests_without_a_bearer_token_are_left_to_other_authenticators() {
+        let jwks = jwks_file();
+
+        assert!(matches!(
+            authenticator(&jwks).authenticate(&HeaderMap::new()),
+            AuthenticationOutcome::NotPresent
+        ));
+    }
+
+    fn api_keys() -> Authentication {
+        Authentication::new(&AuthenticationConfiguration {
+            api_keys: vec![ApiKeyConfiguration {
+                caller: "batch-jobs".to_string(),
+                key: "correct-key".to_string(),
+                organizations: Some(vec![SalesforceOrganization::NationalFunding]),
+            }],
+            jwt: None,
+            allow_anonymous: false,
+        })
+        .unwrap()
+    }
+
+    fn api_key(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_str(key).unwrap());
+        headers
+    }
+
+    #[test]
+    fn api_keys_identify_the_caller_they_were_issued_to() {
+        let caller = api_keys().authenticate(&api_key("correct-key")).unwrap();
+
+        assert_eq!(caller.id, "batch-jobs");
+        assert_eq!(caller.method, AuthenticationMethod::ApiKey);
+        assert!(caller
+            .authorize_organization(SalesforceOrganization::Underwriting)
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_api_keys_are_rejected() {
+        for key in ["wrong-key", "correct-ke", "correct-key2"] {
+            assert!(matches!(
+                api_keys().authenticate(&api_key(key)),
+                Err(ServiceError::Unauthenticated(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn missing_credentials_are_rejected_unless_anonymous_access_is_allowed() {
+        assert!(matches!(
+            api_keys().authenticate(&HeaderMap::new()),
+            Err(ServiceError::Unauthenticated(_))
+        ));
+
+        let anonymous = Authentication::new(&AuthenticationConfiguration {
+            allow_anonymous: true,
+            ..AuthenticationConfiguration::default()
+        })
+        .unwrap();
+        let caller = anonymous.authenticate(&HeaderMap::new()).unwrap();
+        assert_eq!(caller.method, AuthenticationMethod::Anonymous);
+    }
+}
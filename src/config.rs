@@ -1,8 +1,23 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use aws_sdk_ssm::types::Parameter;
 use serde::Deserialize;
 use tracing::info;
 
+use crate::audit::AuditConfiguration;
+use crate::authentication::AuthenticationConfiguration;
 use crate::errors::{ServiceError, ServiceResult};
+use crate::idempotency::IdempotencyConfiguration;
+use crate::organization::SalesforceOrganization;
+use crate::redaction::{RedactionConfiguration, REDACTED};
+use crate::salesforce::circuit_breaker::CircuitBreakerConfiguration;
+use crate::salesforce::field_policy::FieldPolicyConfiguration;
+use crate::salesforce::limits::ApiLimitConfiguration;
+use crate::salesforce::rate_limit::RateLimitConfiguration;
+use crate::salesforce::record_cache::RecordCacheConfiguration;
+use crate::salesforce::retry::RetryPolicy;
+use crate::soql::QueryGuardrailConfiguration;
 
 const UW_SALESFORCE_PARAMETER: &str = "/globals/salesforce/uw";
 
@@ -12,7 +27,7 @@ const QB_SALESFORCE_PARAMETER: &str = "/globals/salesforce.qb.qbautomationuser";
 
 const SERVICE_PARAMETER: &str = "/services/DLPEvent/configSection/App";
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SalesforceConfiguration {
     #[serde(rename = "salesForceUrl")]
@@ -23,6 +38,19 @@ pub struct SalesforceConfiguration {
     pub consumer_secret: String,
 }
 
+// Credentials are masked so the configuration can't leak them through spans or error output
+impl fmt::Debug for SalesforceConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SalesforceConfiguration")
+            .field("salesforce_url", &self.salesforce_url)
+            .field("user_name", &self.user_name)
+            .field("password", &REDACTED)
+            .field("consumer_key", &REDACTED)
+            .field("consumer_secret", &REDACTED)
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ServiceConfiguration {
@@ -30,6 +58,54 @@ pub struct ServiceConfiguration {
     pub timeout_seconds: Option<u64>,
     pub port: Option<u16>,
     pub salesforce_version: Option<String>,
+    pub describe_cache_seconds: Option<u64>,
+    pub readiness_cache_seconds: Option<u64>,
+    pub readiness_probe_limits: Option<bool>,
+    /// Reports the service as unready while any organization is unreachable. Off by default, so
+    /// an outage in one organization doesn't take traffic away from the others.
+    pub readiness_requires_all_organizations: Option<bool>,
+    #[serde(default)]
+    pub organizations: HashMap<SalesforceOrganization, OrganizationConfiguration>,
+    #[serde(default)]
+    pub redaction: RedactionConfiguration,
+    #[serde(default)]
+    pub authentication: AuthenticationConfiguration,
+    /// Path to the authorization policy file. Callers may perform any operation when it's omitted.
+    pub policy_path: Option<String>,
+    #[serde(default)]
+    pub query_guardrails: QueryGuardrailConfiguration,
+    #[serde(default)]
+    pub audit: AuditConfiguration,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfiguration,
+}
+
+impl ServiceConfiguration {
+    /// Settings specific to an organization, falling back to defaults for any that are not configured.
+    pub fn organization(&self, organization: SalesforceOrganization) -> OrganizationConfiguration {
+        self.organizations
+            .get(&organization)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Behavior that can be tuned separately for each Salesforce organization.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OrganizationConfiguration {
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfiguration,
+    #[serde(default)]
+    pub api_limits: ApiLimitConfiguration,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfiguration,
+    #[serde(default)]
+    pub field_policies: FieldPolicyConfiguration,
+    #[serde(default)]
+    pub record_cache: RecordCacheConfiguration,
 }
 
 #[derive(Debug, Clone)]
@@ -72,7 +148,8 @@ where
     }
 }
 
-#[tracing::instrument(ret, skip(client))]
+// The parameters hold credentials, so only the path is recorded
+#[tracing::instrument(skip(client))]
 async fn load_configuration(
     client: aws_sdk_ssm::Client,
     parameter_path: &str,
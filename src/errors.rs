@@ -4,16 +4,142 @@
 use aws_sdk_ssm::error::SdkError;
 use aws_sdk_ssm::operation::get_parameter::GetParameterError;
 use axum::extract::rejection::JsonRejection;
+use axum::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use thiserror::Error;
-use tracing::error;
+
+use crate::organization::SalesforceOrganization;
+use crate::request_context::current_request_id;
+use crate::salesforce::limits::ApiUsage;
+use crate::salesforce::preflight::FieldViolation;
+use crate::soql::SoqlSyntaxError;
 
 /// Wrapped result type useful for marshalling between library and dependencies errors.
 pub type ServiceResult<T> = Result<T, ServiceError>;
 
+/// Error code Salesforce uses when the requested resource does not exist.
+pub const NOT_FOUND_ERROR_CODE: &str = "NOT_FOUND";
+
+/// A single error reported by Salesforce in the body of an unsuccessful response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalesforceApiError {
+    pub error_code: String,
+    pub message: String,
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+/// The OAuth endpoints report errors in their own shape rather than the REST API error list.
+#[derive(Debug, Deserialize)]
+struct OAuthError {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl SalesforceApiError {
+    /// Parses an error response body, which Salesforce sends as a list of errors from the REST API,
+    /// a single error from the OAuth endpoints, or occasionally something that's not JSON at all.
+    pub fn parse(status: reqwest::StatusCode, body: &str) -> Vec<Self> {
+        if let Ok(errors) = serde_json::from_str::<Vec<Self>>(body) {
+            if !errors.is_empty() {
+                return errors;
+            }
+        }
+
+        if let Ok(error) = serde_json::from_str::<Self>(body) {
+            return vec![error];
+        }
+
+        if let Ok(error) = serde_json::from_str::<OAuthError>(body) {
+            return vec![Self {
+                message: error
+                    .error_description
+                    .unwrap_or_else(|| error.error.clone()),
+                error_code: error.error.to_uppercase(),
+                fields: Vec::new(),
+            }];
+        }
+
+        let error_code = match status {
+            reqwest::StatusCode::NOT_FOUND => NOT_FOUND_ERROR_CODE.to_string(),
+            _ => status
+                .canonical_reason()
+                .unwrap_or("UNKNOWN_ERROR")
+                .to_uppercase()
+                .replace(' ', "_"),
+        };
+
+        vec![Self {
+            error_code,
+            message: body.trim().to_string(),
+            fields: Vec::new(),
+        }]
+    }
+}
+
+fn first_error_message(errors: &[SalesforceApiError]) -> String {
+    errors
+        .first()
+        .map(|error| error.message.clone())
+        .unwrap_or_else(|| String::from("Salesforce was unable to process the request."))
+}
+
+/// Determines the status to respond with for a failed Salesforce request based on the first error
+/// code Salesforce reported, falling back to its own status for client errors the caller's request
+/// is responsible for. Anything else, such as a 401 for the service's own session, is a bad gateway.
+fn salesforce_error_status(upstream_status: u16, errors: &[SalesforceApiError]) -> StatusCode {
+    let error_code = errors.first().map(|error| error.error_code.as_str());
+
+    match error_code {
+        Some(
+            "INVALID_FIELD" | "MALFORMED_QUERY" | "INVALID_TYPE" | "INVALID_QUERY_FILTER_OPERATOR",
+        ) => StatusCode::BAD_REQUEST,
+        Some("REQUIRED_FIELD_MISSING" | "FIELD_CUSTOM_VALIDATION_EXCEPTION") => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        Some("ENTITY_IS_DELETED") => StatusCode::GONE,
+        Some("REQUEST_LIMIT_EXCEEDED") => StatusCode::TOO_MANY_REQUESTS,
+        Some(
+            "INSUFFICIENT_ACCESS"
+            | "INSUFFICIENT_ACCESS_OR_READONLY"
+            | "INSUFFICIENT_ACCESS_ON_CROSS_REFERENCE_ENTITY",
+        ) => StatusCode::FORBIDDEN,
+        Some(NOT_FOUND_ERROR_CODE) => StatusCode::NOT_FOUND,
+        _ => match StatusCode::from_u16(upstream_status) {
+            Ok(
+                status @ (StatusCode::BAD_REQUEST
+                | StatusCode::NOT_FOUND
+                | StatusCode::METHOD_NOT_ALLOWED
+                | StatusCode::CONFLICT
+                | StatusCode::GONE
+                | StatusCode::PRECONDITION_FAILED
+                | StatusCode::PAYLOAD_TOO_LARGE
+                | StatusCode::URI_TOO_LONG
+                | StatusCode::UNSUPPORTED_MEDIA_TYPE
+                | StatusCode::UNPROCESSABLE_ENTITY
+                | StatusCode::PRECONDITION_REQUIRED
+                | StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE),
+            ) => status,
+            _ => StatusCode::BAD_GATEWAY,
+        },
+    }
+}
+
+/// Attaches the id of the request being handled, letting callers quote it when reporting a failure.
+fn error_body(mut body: Value) -> Json<Value> {
+    if let (Value::Object(fields), Some(request_id)) = (&mut body, current_request_id()) {
+        fields.insert("requestId".to_string(), Value::String(request_id));
+    }
+
+    Json(body)
+}
+
 /// Errors that can occur within the client, including mapped errors from reqwest.
 #[derive(Debug, Error)]
 pub enum ServiceError {
@@ -25,7 +151,7 @@ pub enum ServiceError {
     ParameterConfigurationNameEmpty,
     /// Represents a generic error when attempting to retrieve configuration from SSM.
     #[error(transparent)]
-    ParameterConfigurationFailedToLoad(#[from] SdkError<GetParameterError>),
+    ParameterConfigurationFailedToLoad(Box<SdkError<GetParameterError>>),
     /// Represents an invalid empty configuration error.
     #[error("Parameter configuration {0} is empty.")]
     ParameterConfigurationEmpty(String),
@@ -41,20 +167,90 @@ pub enum ServiceError {
     /// Represents a failure when loading application configuration from SSM at startup.
     #[error(transparent)]
     JsonParsingError(#[from] JsonRejection),
+    /// Represents a failure when configuring trace export at startup.
+    #[error(transparent)]
+    TelemetryInitializationFailed(#[from] opentelemetry::trace::TraceError),
     #[error("Access token was not found on the configuration.")]
     AccessTokenNotFound,
     #[error("A Salesforce instance URL was not found.")]
     InstanceUrlNotFound,
     #[error("{0}")]
     AuthenticationLockFailed(String),
+    /// Represents Salesforce refusing or failing to issue an access token, the details of which are
+    /// only logged as they describe the service's credentials rather than the caller's request.
+    #[error("Authentication with Salesforce failed.")]
+    AuthenticationFailed,
     #[error("{0}")]
     ObjectRetrievalFailed(String),
     #[error("Object was not found.")]
     ObjectNotFound,
     #[error("{0}")]
     InvalidOrganization(String),
-    #[error("An error occurred while attempting to update the object.")]
-    ObjectUpdateFailed(Value),
+    #[error("{}", first_error_message(.1))]
+    SalesforceRequestFailed(u16, Vec<SalesforceApiError>),
+    #[error("{0}")]
+    BulkJobFailed(String),
+    #[error("Bulk job {0} did not complete in time.")]
+    BulkJobTimedOut(String),
+    #[error("{0}")]
+    CsvConversionFailed(String),
+    #[error("The request payload is not valid for {0}.")]
+    PayloadInvalid(String, Vec<FieldViolation>),
+    #[error("{0} has no relationship named {1}.")]
+    RelationshipNotFound(String, String),
+    #[error("{0} {1} has no related {2}.")]
+    RelatedRecordNotFound(String, String, String),
+    #[error("The cursor is not valid for this relationship.")]
+    CursorInvalid,
+    #[error("{0}")]
+    PathParameterInvalid(String),
+    #[error("The fields selected are not valid for {0}.")]
+    FieldSelectionInvalid(String, Vec<FieldViolation>),
+    #[error("Salesforce organization {0} is currently unavailable.")]
+    OrganizationUnavailable(SalesforceOrganization, u64),
+    #[error("Salesforce organization {0} is close to its daily API limit, only critical requests are being accepted.")]
+    ApiLimitThresholdExceeded(SalesforceOrganization, ApiUsage),
+    #[error("Too many requests are queued for Salesforce organization {0}.")]
+    RateLimited(SalesforceOrganization, u64),
+    #[error("{0}")]
+    Unauthenticated(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("The query is not valid SOQL: {0}")]
+    QuerySyntaxInvalid(SoqlSyntaxError),
+    #[error("{0}")]
+    QueryRejected(String),
+    #[error("The record has been modified since the version the update was based on.")]
+    PreconditionFailed,
+    #[error("{0}")]
+    IdempotencyKeyInvalid(String),
+    #[error("The idempotency key has already been used for a different request.")]
+    IdempotencyKeyReused,
+    #[error("A request with the idempotency key is still in progress.")]
+    IdempotencyKeyInFlight,
+    #[error("The request body is too large.")]
+    RequestBodyTooLarge,
+    /// Represents a failure reading from or writing to the idempotency key store.
+    #[error("{0}")]
+    IdempotencyStoreFailed(String),
+    /// Represents a failure opening or writing to the audit log.
+    #[error("{0}")]
+    AuditLogFailed(String),
+    /// Represents invalid caller authentication or authorization settings found at startup.
+    #[error("{0}")]
+    AuthenticationConfigurationInvalid(String),
+}
+
+impl From<csv::Error> for ServiceError {
+    fn from(err: csv::Error) -> Self {
+        Self::CsvConversionFailed(err.to_string())
+    }
+}
+
+impl From<SdkError<GetParameterError>> for ServiceError {
+    fn from(err: SdkError<GetParameterError>) -> Self {
+        Self::ParameterConfigurationFailedToLoad(Box::new(err))
+    }
 }
 
 impl IntoResponse for ServiceError {
@@ -74,9 +270,112 @@ impl IntoResponse for ServiceError {
                 (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
             }
             Self::RequestInvalid(err) => (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()),
+            Self::Unauthenticated(message) => {
+                let body = json!({ "message": message });
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(WWW_AUTHENTICATE, "Bearer")],
+                    error_body(body),
+                )
+                    .into_response();
+            }
+            Self::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+            Self::QuerySyntaxInvalid(error) => {
+                let body = json!({
+                    "message": Self::QuerySyntaxInvalid(error.clone()).to_string(),
+                    "position": error.position,
+                });
+                return (StatusCode::UNPROCESSABLE_ENTITY, error_body(body)).into_response();
+            }
+            Self::QueryRejected(message) => (StatusCode::UNPROCESSABLE_ENTITY, message),
+            Self::PreconditionFailed => (
+                StatusCode::PRECONDITION_FAILED,
+                Self::PreconditionFailed.to_string(),
+            ),
+            Self::IdempotencyKeyInvalid(message) => (StatusCode::BAD_REQUEST, message),
+            Self::IdempotencyKeyReused => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Self::IdempotencyKeyReused.to_string(),
+            ),
+            Self::IdempotencyKeyInFlight => (
+                StatusCode::CONFLICT,
+                Self::IdempotencyKeyInFlight.to_string(),
+            ),
+            Self::RequestBodyTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Self::RequestBodyTooLarge.to_string(),
+            ),
             Self::ObjectNotFound => (StatusCode::NOT_FOUND, Self::ObjectNotFound.to_string()),
-            Self::ObjectUpdateFailed(err) => {
-                return (StatusCode::INTERNAL_SERVER_ERROR, Json(err)).into_response();
+            Self::RelationshipNotFound(object, relationship) => (
+                StatusCode::NOT_FOUND,
+                Self::RelationshipNotFound(object, relationship).to_string(),
+            ),
+            Self::RelatedRecordNotFound(object, id, relationship) => (
+                StatusCode::NOT_FOUND,
+                Self::RelatedRecordNotFound(object, id, relationship).to_string(),
+            ),
+            Self::CursorInvalid => (StatusCode::BAD_REQUEST, Self::CursorInvalid.to_string()),
+            Self::PathParameterInvalid(message) => (StatusCode::BAD_REQUEST, message),
+            Self::AuthenticationFailed => (
+                StatusCode::BAD_GATEWAY,
+                Self::AuthenticationFailed.to_string(),
+            ),
+            Self::BulkJobFailed(message) => (StatusCode::BAD_GATEWAY, message),
+            Self::BulkJobTimedOut(job_id) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                Self::BulkJobTimedOut(job_id).to_string(),
+            ),
+            Self::CsvConversionFailed(message) => (StatusCode::UNPROCESSABLE_ENTITY, message),
+            Self::PayloadInvalid(object, violations) => {
+                let body = json!({
+                    "message": Self::PayloadInvalid(object, Vec::new()).to_string(),
+                    "errors": violations,
+                });
+                return (StatusCode::UNPROCESSABLE_ENTITY, error_body(body)).into_response();
+            }
+            Self::FieldSelectionInvalid(object, violations) => {
+                let body = json!({
+                    "message": Self::FieldSelectionInvalid(object, Vec::new()).to_string(),
+                    "errors": violations,
+                });
+                return (StatusCode::UNPROCESSABLE_ENTITY, error_body(body)).into_response();
+            }
+            Self::OrganizationUnavailable(organization, retry_after) => {
+                let body = json!({
+                    "message": Self::OrganizationUnavailable(organization, retry_after).to_string()
+                });
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(RETRY_AFTER, retry_after.to_string())],
+                    error_body(body),
+                )
+                    .into_response();
+            }
+            Self::RateLimited(organization, retry_after) => {
+                let body = json!({
+                    "message": Self::RateLimited(organization, retry_after).to_string()
+                });
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after.to_string())],
+                    error_body(body),
+                )
+                    .into_response();
+            }
+            Self::ApiLimitThresholdExceeded(organization, usage) => {
+                let body = json!({
+                    "message": Self::ApiLimitThresholdExceeded(organization, usage).to_string(),
+                    "apiUsage": usage,
+                });
+                return (StatusCode::TOO_MANY_REQUESTS, error_body(body)).into_response();
+            }
+            Self::SalesforceRequestFailed(upstream_status, errors) => {
+                let body = json!({
+                    "message": first_error_message(&errors),
+                    "errors": errors,
+                });
+                let status = salesforce_error_status(upstream_status, &errors);
+                return (status, error_body(body)).into_response();
             }
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -88,6 +387,6 @@ impl IntoResponse for ServiceError {
             "message": error_message
         });
 
-        (status, Json(body)).into_response()
+        (status, error_body(body)).into_response()
     }
 }
@@ -0,0 +1,27 @@
+use async_trait::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+
+use crate::authentication::Caller;
+use crate::errors::ServiceError;
+
+/// The caller identified by the authentication middleware.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedCaller(pub Caller);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedCaller
+where
+    S: Send + Sync,
+{
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<Caller>() {
+            None => Err(ServiceError::Unauthenticated(
+                "Credentials are required to access this resource.".to_string(),
+            )),
+            Some(caller) => Ok(AuthenticatedCaller(caller.clone())),
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::extract::{FromRequest, FromRequestParts, Path, Request};
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::Value;
+
+use crate::audit::{AuditEntry, AuditOperation};
+use crate::authentication::Caller;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::extractors::resolve_service::ResolveSalesforceServiceFromService;
+use crate::organization::SalesforceOrganization;
+use crate::policy::{FieldAccess, PolicyOperation};
+use crate::router::RouterState;
+use crate::salesforce::describe::Relationship;
+use crate::salesforce::service::SalesforceService;
+use crate::soql::{self, SoqlQuery};
+
+/// An operation handlers can require callers to have been granted.
+pub trait PolicyAction: Debug + Send + Sync {
+    const OPERATION: PolicyOperation;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReadAction;
+
+impl PolicyAction for ReadAction {
+    const OPERATION: PolicyOperation = PolicyOperation::Read;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UpdateAction;
+
+impl PolicyAction for UpdateAction {
+    const OPERATION: PolicyOperation = PolicyOperation::Update;
+}
+
+/// Checks the operation against the authorization policy for the object named in the path,
+/// exposing the fields the caller may access on it.
+#[derive(Debug, Clone)]
+pub struct Authorized<A> {
+    pub fields: FieldAccess,
+    action: PhantomData<A>,
+}
+
+#[async_trait]
+impl<A: PolicyAction> FromRequestParts<Arc<RouterState>> for Authorized<A> {
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<RouterState>,
+    ) -> Result<Self, Self::Rejection> {
+        let parameters = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .ok();
+
+        let object = parameters
+            .as_ref()
+            .and_then(|Path(parameters)| parameters.get("name"))
+            .ok_or_else(|| {
+                ServiceError::Forbidden("The requested object could not be determined.".to_string())
+            })?;
+
+        let fields = authorize(
+            parts.extensions.get::<Caller>(),
+            &parts.headers,
+            state,
+            object,
+            A::OPERATION,
+        )?;
+
+        Ok(Self {
+            fields,
+            action: PhantomData,
+        })
+    }
+}
+
+/// A record payload the caller is permitted to write every field of.
+#[derive(Debug, Clone)]
+pub struct AuthorizedJson<A> {
+    pub payload: Value,
+    action: PhantomData<A>,
+}
+
+#[async_trait]
+impl<A: PolicyAction> FromRequest<Arc<RouterState>> for AuthorizedJson<A> {
+    type Rejection = ServiceError;
+
+    async fn from_request(req: Request, state: &Arc<RouterState>) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let caller = parts.extensions.get::<Caller>().cloned();
+        let headers = parts.headers.clone();
+        let parameters = Path::<HashMap<String, String>>::from_request_parts(&mut parts, state)
+            .await
+            .map(|Path(parameters)| parameters)
+            .unwrap_or_default();
+
+        let authorized = match Authorized::<A>::from_request_parts(&mut parts, state).await {
+            Ok(authorized) => authorized,
+            Err(e) => {
+                audit_denied::<A>(
+                    caller.as_ref(),
+                    &headers,
+                    &parameters,
+                    state,
+                    &Value::Null,
+                    &e,
+                )
+                .await;
+                return Err(e);
+            }
+        };
+
+        let Json(payload) =
+            Json::<Value>::from_request(Request::from_parts(parts, body), state).await?;
+
+        if let Value::Object(fields) = &payload {
+            let forbidden = authorized.fields.forbidden(
+                fields
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|name| *name != "attributes"),
+            );
+
+            if !forbidden.is_empty() {
+                let e = ServiceError::Forbidden(format!(
+                    "Writing {} is not permitted.",
+                    forbidden.join(", ")
+                ));
+                audit_denied::<A>(caller.as_ref(), &headers, &parameters, state, &payload, &e)
+                    .await;
+                return Err(e);
+            }
+        }
+
+        Ok(Self {
+            payload,
+            action: PhantomData,
+        })
+    }
+}
+
+/// Audits a write the caller wasn't permitted to make, which is rejected before it reaches the
+/// handler that would otherwise audit it.
+async fn audit_denied<A: PolicyAction>(
+    caller: Option<&Caller>,
+    headers: &HeaderMap,
+    parameters: &HashMap<String, String>,
+    state: &Arc<RouterState>,
+    payload: &Value,
+    error: &ServiceError,
+) {
+    let (Some(caller), Some(operation), ServiceError::Forbidden(message)) = (
+        caller,
+        AuditOperation::for_policy_operation(A::OPERATION),
+        error,
+    ) else {
+        return;
+    };
+    let Some(organization) = headers
+        .get("SF-Organization")
+        .and_then(|header| SalesforceOrganization::try_from(header).ok())
+    else {
+        return;
+    };
+
+    let entry = AuditEntry::new(
+        caller,
+        organization,
+        operation,
+        parameters.get("name").map_or("", String::as_str),
+        parameters.get("id").map(String::as_str),
+        payload,
+    )
+    .with_result::<()>(&Err(ServiceError::Forbidden(message.clone())));
+
+    state.audit.record(entry).await;
+}
+
+/// A syntactically valid SOQL query touching only objects and fields the caller may query.
+#[derive(Debug, Clone)]
+pub struct AuthorizedSoql {
+    pub soql: String,
+    pub query: SoqlQuery,
+    pub fields: FieldAccess,
+}
+
+#[async_trait]
+impl FromRequest<Arc<RouterState>> for AuthorizedSoql {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &Arc<RouterState>) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let caller = parts.extensions.get::<Caller>().cloned();
+        let headers = parts.headers.clone();
+        let ResolveSalesforceServiceFromService(service) =
+            ResolveSalesforceServiceFromService::from_request_parts(&mut parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+        let soql = String::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let query =
+            soql::parse(&soql).map_err(|e| ServiceError::QuerySyntaxInvalid(e).into_response())?;
+
+        let fields = authorize_query(caller.as_ref(), &headers, state, &service, &query)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Self {
+            soql,
+            query,
+            fields,
+        })
+    }
+}
+
+/// Authorizes the queried object and every field the query references. Subqueries, semi-joins,
+/// relationship traversals and `TYPEOF` branches read from other objects, which describe metadata
+/// resolves so they're authorized the same way, with each relationship followed granted as a field
+/// of the object it leads from.
+async fn authorize_query(
+    caller: Option<&Caller>,
+    headers: &HeaderMap,
+    state: &Arc<RouterState>,
+    service: &SalesforceService,
+    query: &SoqlQuery,
+) -> ServiceResult<FieldAccess> {
+    let fields = authorize(
+        caller,
+        headers,
+        state,
+        &query.object,
+        PolicyOperation::Query,
+    )?;
+    if state.policy.is_none() {
+        return Ok(fields);
+    }
+
+    let authorization = QueryAuthorization {
+        caller,
+        headers,
+        state,
+        service,
+    };
+    let mut pending = vec![(query.object.clone(), query)];
+
+    while let Some((object, query)) = pending.pop() {
+        authorize(caller, headers, state, &object, PolicyOperation::Query)?;
+
+        let mut forbidden = Vec::new();
+        for field in query.referenced_fields() {
+            if !authorization.allows(&object, field).await? {
+                forbidden.push(field.to_string());
+            }
+        }
+
+        for branch in &query.typeof_branches {
+            for field in &branch.fields {
+                let path = format!("{}.{field}", branch.relationship);
+                let allowed = match &branch.object {
+                    Some(referenced) => {
+                        authorization.allows(&object, &branch.relationship).await?
+                            && authorization.allows(referenced, field).await?
+                    }
+                    None => authorization.allows(&object, &path).await?,
+                };
+                if !allowed {
+                    forbidden.push(path);
+                }
+            }
+        }
+
+        // Subqueries in the field list name a child relationship rather than the object
+        if !query.subqueries.is_empty() {
+            let describe = service.describe_object(object.clone()).await?;
+            for subquery in &query.subqueries {
+                match describe.relationship(&subquery.object) {
+                    Some(Relationship::Child { object: child, .. })
+                        if authorization.allows(&object, &subquery.object).await?
+                            && authorization.access(&child)?.is_some() =>
+                    {
+                        pending.push((child, subquery));
+                    }
+                    _ => forbidden.push(subquery.object.clone()),
+                }
+            }
+        }
+
+        if !forbidden.is_empty() {
+            return Err(ServiceError::Forbidden(format!(
+                "Querying {} on {} is not permitted.",
+                forbidden.join(", "),
+                query.object
+            )));
+        }
+
+        pending.extend(
+            query
+                .semi_joins
+                .iter()
+                .map(|semi_join| (semi_join.object.clone(), semi_join)),
+        );
+    }
+
+    Ok(fields)
+}
+
+struct QueryAuthorization<'a> {
+    caller: Option<&'a Caller>,
+    headers: &'a HeaderMap,
+    state: &'a Arc<RouterState>,
+    service: &'a SalesforceService,
+}
+
+impl QueryAuthorization<'_> {
+    /// The fields the caller may query on the object, or `None` when they may not query it at all.
+    fn access(&self, object: &str) -> ServiceResult<Option<FieldAccess>> {
+        match authorize(
+            self.caller,
+            self.headers,
+            self.state,
+            object,
+            PolicyOperation::Query,
+        ) {
+            Ok(fields) => Ok(Some(fields)),
+            Err(ServiceError::Forbidden(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the caller may query a field of the object, following traversals like
+    /// `Account.Owner.Email` through every object the relationships may reference. Relationships
+    /// describe metadata doesn't know can't be authorized, so aren't allowed.
+    async fn allows(&self, object: &str, path: &str) -> ServiceResult<bool> {
+        let mut relationships: Vec<&str> = path.split('.').collect();
+        let field = relationships.pop().unwrap_or(path);
+
+        let mut objects = vec![object.to_string()];
+        for relationship in relationships {
+            let mut targets = Vec::new();
+            for object in objects {
+                if !self.allows_field(&object, relationship)? {
+                    return Ok(false);
+                }
+
+                let describe = self.service.describe_object(object).await?;
+                match describe.lookup_field(relationship) {
+                    Some(lookup) => targets.extend(lookup.reference_to.iter().cloned()),
+                    None => return Ok(false),
+                }
+            }
+            objects = targets;
+        }
+
+        for object in &objects {
+            if !self.allows_field(object, field)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn allows_field(&self, object: &str, field: &str) -> ServiceResult<bool> {
+        Ok(self
+            .access(object)?
+            .is_some_and(|fields| fields.allows(field)))
+    }
+}
+
+/// Authorizes the caller against the policy, permitting everything when none is configured.
+pub(crate) fn authorize(
+    caller: Option<&Caller>,
+    headers: &HeaderMap,
+    state: &Arc<RouterState>,
+    object: &str,
+    operation: PolicyOperation,
+) -> ServiceResult<FieldAccess> {
+    let Some(policy) = &state.policy else {
+        return Ok(FieldAccess::All);
+    };
+
+    let caller = caller.ok_or_else(|| {
+        ServiceError::Unauthenticated(
+            "Credentials are required to access this resource.".to_string(),
+        )
+    })?;
+
+    let organization = headers
+        .get("SF-Organization")
+        .ok_or_else(|| {
+            ServiceError::InvalidOrganization(
+                "Salesforce organization header was not found.".to_string(),
+            )
+        })
+        .and_then(SalesforceOrganization::try_from)?;
+
+    policy.authorize(caller, organization, object, operation)
+}
@@ -1,3 +1,6 @@
+pub mod authenticated_caller;
+pub mod authorize;
 pub mod extract_org;
+pub mod preconditions;
 pub mod resolve_service;
 pub mod validation;
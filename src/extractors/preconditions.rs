@@ -0,0 +1,36 @@
+use std::convert::Infallible;
+
+use async_trait::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::{IF_MATCH, IF_UNMODIFIED_SINCE};
+use axum::http::request::Parts;
+
+use crate::salesforce::concurrency::WritePreconditions;
+
+/// The `If-Match` and `If-Unmodified-Since` conditions sent with a write, passed through to
+/// Salesforce as-is.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractPreconditions(pub WritePreconditions);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ExtractPreconditions
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Ok(ExtractPreconditions(WritePreconditions {
+            if_match: header(IF_MATCH),
+            if_unmodified_since: header(IF_UNMODIFIED_SINCE),
+        }))
+    }
+}
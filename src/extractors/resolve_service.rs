@@ -5,6 +5,7 @@ use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 use axum::http::HeaderMap;
 
+use crate::authentication::Caller;
 use crate::errors::{ServiceError, ServiceResult};
 use crate::organization::SalesforceOrganization;
 use crate::router::RouterState;
@@ -34,6 +35,16 @@ impl FromRequestParts<Arc<RouterState>> for ResolveSalesforceServiceFromService
 
                     match org {
                         Ok(parsed_org) => {
+                            match parts.extensions.get::<Caller>() {
+                                None => {
+                                    return Err(ServiceError::Unauthenticated(
+                                        "Credentials are required to access this resource."
+                                            .to_string(),
+                                    ))
+                                }
+                                Some(caller) => caller.authorize_organization(parsed_org)?,
+                            }
+
                             let resolved_service = state.resolver.resolve(parsed_org);
                             Ok(ResolveSalesforceServiceFromService(resolved_service))
                         }
@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
 use async_trait::async_trait;
-use axum::extract::rejection::JsonRejection;
-use axum::extract::{FromRequest, Request};
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{FromRequest, Path, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 use axum::Json;
+use regex::Regex;
 use serde::de::DeserializeOwned;
 use validator::Validate;
 
@@ -25,3 +31,47 @@ where
         Ok(ValidatedJson(value))
     }
 }
+
+/// Rejects requests whose object, relationship or record id path parameters aren't plain API
+/// names and ids. Parameters are decoded before they're interpolated into Salesforce URLs, so an
+/// encoded `/`, `?` or `..` would otherwise let a request authorized for one object reach another
+/// resource entirely.
+pub async fn validate_path_parameters(
+    parameters: Result<Path<HashMap<String, String>>, PathRejection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let parameters = match parameters {
+        Ok(Path(parameters)) => parameters,
+        Err(PathRejection::MissingPathParams(_)) => HashMap::new(),
+        Err(_) => {
+            return ServiceError::PathParameterInvalid("The request path is not valid.".to_string())
+                .into_response()
+        }
+    };
+
+    for (name, value) in &parameters {
+        let pattern = match name.as_str() {
+            "name" | "relationship" => api_name_pattern(),
+            "id" => id_pattern(),
+            _ => continue,
+        };
+
+        if !pattern.is_match(value) {
+            return ServiceError::PathParameterInvalid(format!("{value} is not a valid {name}."))
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+fn api_name_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap())
+}
+
+fn id_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[a-zA-Z0-9]{15}([a-zA-Z0-9]{3})?$").unwrap())
+}
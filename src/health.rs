@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use futures::future::join_all;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::config::ServiceConfiguration;
+use crate::salesforce::circuit_breaker::CircuitSnapshot;
+use crate::salesforce::limits::ApiUsage;
+use crate::salesforce::resolver::SalesforceServiceResolver;
+
+const DEFAULT_READINESS_CACHE_SECONDS: u64 = 15;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationReadiness {
+    pub ready: bool,
+    pub authenticated: bool,
+    pub circuit: CircuitSnapshot,
+    pub api_usage: Option<ApiUsage>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessReport {
+    /// Whether the service can accept traffic, which only depends on the organizations when
+    /// configured to require all of them.
+    pub ready: bool,
+    pub configuration_loaded: bool,
+    pub all_organizations_ready: bool,
+    pub organizations: BTreeMap<String, OrganizationReadiness>,
+}
+
+/// Checks whether each organization can be reached, caching the result so load balancer probes
+/// don't turn into a steady stream of calls to Salesforce.
+#[derive(Debug)]
+pub struct ReadinessCheck {
+    cache_duration: Duration,
+    probe_limits: bool,
+    requires_all_organizations: bool,
+    cached: Mutex<Option<(Instant, ReadinessReport)>>,
+}
+
+impl ReadinessCheck {
+    pub fn new(service_configuration: &ServiceConfiguration) -> Self {
+        Self {
+            cache_duration: Duration::from_secs(
+                service_configuration
+                    .readiness_cache_seconds
+                    .unwrap_or(DEFAULT_READINESS_CACHE_SECONDS),
+            ),
+            probe_limits: service_configuration
+                .readiness_probe_limits
+                .unwrap_or(false),
+            requires_all_organizations: service_configuration
+                .readiness_requires_all_organizations
+                .unwrap_or(false),
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn check(&self, resolver: &SalesforceServiceResolver) -> ReadinessReport {
+        // Holding the lock while checking means concurrent probes share a single round of calls
+        let mut cached = self.cached.lock().await;
+
+        if let Some((checked_at, report)) = cached.as_ref() {
+            if checked_at.elapsed() < self.cache_duration {
+                return report.clone();
+            }
+        }
+
+        info!("Checking readiness of all organizations");
+
+        let checks = resolver
+            .services()
+            .into_iter()
+            .map(|(organization, service)| async move {
+                let mut error = None;
+
+                let authenticated = match service.authenticate().await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        error = Some(e.to_string());
+                        false
+                    }
+                };
+
+                if authenticated && self.probe_limits {
+                    if let Err(e) = service.get_limits().await {
+                        error = Some(e.to_string());
+                    }
+                }
+
+                let circuit = service.circuit_state();
+                let ready = authenticated && error.is_none() && !circuit.is_open();
+
+                if !ready {
+                    warn!("{organization} is not ready: {error:?}");
+                }
+
+                (
+                    organization.to_string(),
+                    OrganizationReadiness {
+                        ready,
+                        authenticated,
+                        circuit,
+                        api_usage: service.api_usage(),
+                        error,
+                    },
+                )
+            });
+
+        let organizations: BTreeMap<String, OrganizationReadiness> =
+            join_all(checks).await.into_iter().collect();
+        let all_organizations_ready = organizations.values().all(|readiness| readiness.ready);
+        // The configuration was loaded before the router was built, so the process itself is ready
+        let configuration_loaded = true;
+        let report = ReadinessReport {
+            ready: configuration_loaded
+                && (all_organizations_ready || !self.requires_all_organizations),
+            configuration_loaded,
+            all_organizations_ready,
+            organizations,
+        };
+
+        *cached = Some((Instant::now(), report.clone()));
+
+        report
+    }
+}
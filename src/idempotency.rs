@@ -0,0 +1,459 @@
+//! Idempotent writes, so callers can safely retry a create or update after a timeout. A write sent
+//! with an `Idempotency-Key` header has its response stored, and repeats of it with the same key
+//! get the stored response back instead of being sent to Salesforce again, e.g.
+//! `"Idempotency": { "TtlSeconds": 86400, "Store": { "Type": "Sqlite", "Path": "idempotency.db" } }`.
+//! A repeat arriving while the first request is still in progress waits for it up to
+//! `InFlightWaitMillis`, then gets a 409 so it can retry later.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use tokio::sync::OwnedMutexGuard;
+use tracing::{error, info};
+
+use crate::authentication::Caller;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::router::RouterState;
+
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Set on responses replayed from the store rather than produced by the request itself.
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Matches the limit axum applies to request bodies by default.
+const MAX_REQUEST_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+const DEFAULT_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+const DEFAULT_IN_FLIGHT_WAIT_MILLIS: u64 = 10_000;
+
+fn default_ttl_seconds() -> u64 {
+    DEFAULT_TTL_SECONDS
+}
+
+fn default_in_flight_wait_millis() -> u64 {
+    DEFAULT_IN_FLIGHT_WAIT_MILLIS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IdempotencyConfiguration {
+    /// How long a response is replayed for after the request that produced it.
+    #[serde(default = "default_ttl_seconds")]
+    pub ttl_seconds: u64,
+    /// How long a repeat waits for a request with the same key that's still in progress.
+    #[serde(default = "default_in_flight_wait_millis")]
+    pub in_flight_wait_millis: u64,
+    #[serde(default)]
+    pub store: IdempotencyStoreConfiguration,
+}
+
+impl Default for IdempotencyConfiguration {
+    fn default() -> Self {
+        Self {
+            ttl_seconds: DEFAULT_TTL_SECONDS,
+            in_flight_wait_millis: DEFAULT_IN_FLIGHT_WAIT_MILLIS,
+            store: IdempotencyStoreConfiguration::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "Type")]
+pub enum IdempotencyStoreConfiguration {
+    /// Keeps responses in memory, so they're lost when the service restarts.
+    #[default]
+    Memory,
+    /// Keeps responses in the `idempotency_keys` table of a SQLite database, creating it if
+    /// necessary.
+    Sqlite {
+        #[serde(rename = "Path")]
+        path: String,
+    },
+}
+
+/// A response as it was first returned for an idempotency key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    /// Hash of the request body, to tell a retry apart from a different request reusing the key.
+    pub fingerprint: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl IntoResponse for StoredResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut response = (status, self.body).into_response();
+        let headers = response.headers_mut();
+
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                headers.insert(name, value);
+            }
+        }
+        headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+        response
+    }
+}
+
+/// Where stored responses are kept. Expiry times are Unix timestamps in seconds.
+pub trait IdempotencyStore: fmt::Debug + Send + Sync {
+    fn get(&self, key: &str, now: i64) -> ServiceResult<Option<StoredResponse>>;
+
+    fn put(&self, key: &str, response: &StoredResponse, expires_at: i64) -> ServiceResult<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryIdempotencyStore {
+    responses: Mutex<HashMap<String, (StoredResponse, i64)>>,
+}
+
+impl IdempotencyStore for MemoryIdempotencyStore {
+    fn get(&self, key: &str, now: i64) -> ServiceResult<Option<StoredResponse>> {
+        let responses = self.responses.lock().unwrap();
+
+        Ok(responses
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(response, _)| response.clone()))
+    }
+
+    fn put(&self, key: &str, response: &StoredResponse, expires_at: i64) -> ServiceResult<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut responses = self.responses.lock().unwrap();
+        responses.retain(|_, (_, expires_at)| *expires_at > now);
+        responses.insert(key.to_string(), (response.clone(), expires_at));
+
+        Ok(())
+    }
+}
+
+pub struct SqliteIdempotencyStore {
+    connection: Mutex<Connection>,
+}
+
+impl fmt::Debug for SqliteIdempotencyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteIdempotencyStore")
+            .finish_non_exhaustive()
+    }
+}
+
+impl SqliteIdempotencyStore {
+    pub fn open(path: &str) -> ServiceResult<Self> {
+        let connection = Connection::open(path).map_err(store_failed)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS idempotency_keys (
+                    key TEXT PRIMARY KEY,
+                    fingerprint TEXT NOT NULL,
+                    status INTEGER NOT NULL,
+                    headers TEXT NOT NULL,
+                    body BLOB NOT NULL,
+                    expires_at INTEGER NOT NULL
+                )",
+                (),
+            )
+            .map_err(store_failed)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl IdempotencyStore for SqliteIdempotencyStore {
+    fn get(&self, key: &str, now: i64) -> ServiceResult<Option<StoredResponse>> {
+        let row = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT fingerprint, status, headers, body FROM idempotency_keys
+                WHERE key = ?1 AND expires_at > ?2",
+                params![key, now],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, u16>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Vec<u8>>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(store_failed)?;
+
+        row.map(|(fingerprint, status, headers, body)| {
+            Ok(StoredResponse {
+                fingerprint,
+                status,
+                headers: serde_json::from_str(&headers)?,
+                body,
+            })
+        })
+        .transpose()
+    }
+
+    fn put(&self, key: &str, response: &StoredResponse, expires_at: i64) -> ServiceResult<()> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let headers = serde_json::to_string(&response.headers)?;
+        let connection = self.connection.lock().unwrap();
+
+        connection
+            .execute(
+                "DELETE FROM idempotency_keys WHERE expires_at <= ?1",
+                params![now],
+            )
+            .map_err(store_failed)?;
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO idempotency_keys
+                    (key, fingerprint, status, headers, body, expires_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    key,
+                    response.fingerprint,
+                    response.status,
+                    headers,
+                    response.body,
+                    expires_at,
+                ],
+            )
+            .map(|_| ())
+            .map_err(store_failed)
+    }
+}
+
+fn store_failed(e: impl fmt::Display) -> ServiceError {
+    ServiceError::IdempotencyStoreFailed(e.to_string())
+}
+
+#[derive(Debug)]
+pub struct Idempotency {
+    store: Box<dyn IdempotencyStore>,
+    ttl_seconds: i64,
+    in_flight_wait: Duration,
+    /// Keys with a request in progress, which repeats of the request wait on.
+    in_flight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Idempotency {
+    pub fn new(config: &IdempotencyConfiguration) -> ServiceResult<Self> {
+        let idempotency = match &config.store {
+            IdempotencyStoreConfiguration::Memory => {
+                Self::with_store(MemoryIdempotencyStore::default(), config.ttl_seconds)
+            }
+            IdempotencyStoreConfiguration::Sqlite { path } => {
+                Self::with_store(SqliteIdempotencyStore::open(path)?, config.ttl_seconds)
+            }
+        };
+
+        Ok(idempotency.with_in_flight_wait(Duration::from_millis(config.in_flight_wait_millis)))
+    }
+
+    pub fn with_store(store: impl IdempotencyStore + 'static, ttl_seconds: u64) -> Self {
+        Self {
+            store: Box::new(store),
+            ttl_seconds: i64::try_from(ttl_seconds).unwrap_or(i64::MAX),
+            in_flight_wait: Duration::from_millis(DEFAULT_IN_FLIGHT_WAIT_MILLIS),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_in_flight_wait(mut self, in_flight_wait: Duration) -> Self {
+        self.in_flight_wait = in_flight_wait;
+        self
+    }
+
+    /// Runs the request unless a response is already stored for its key, storing the response
+    /// it produces. Server errors and rate limiting aren't stored, so the request can be retried.
+    async fn handle(&self, key: &str, request: Request, next: Next) -> ServiceResult<Response> {
+        let (parts, body) = request.into_parts();
+        let body = to_bytes(body, MAX_REQUEST_BODY_BYTES)
+            .await
+            .map_err(|_| ServiceError::RequestBodyTooLarge)?;
+        let fingerprint = format!("{:x}", Sha256::digest(&body));
+
+        // Keys are only unique to a caller, and to the write they were first used for
+        let caller = parts
+            .extensions
+            .get::<Caller>()
+            .map(|caller| caller.id.as_str())
+            .unwrap_or_default();
+        let organization = parts
+            .headers
+            .get("SF-Organization")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let scoped_key = format!(
+            "{caller}:{organization}:{} {}:{key}",
+            parts.method, parts.uri
+        );
+
+        let _in_flight = self.begin(&scoped_key).await?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if let Some(stored) = self.store.get(&scoped_key, now)? {
+            if stored.fingerprint != fingerprint {
+                return Err(ServiceError::IdempotencyKeyReused);
+            }
+
+            info!("Replaying the stored response for idempotency key {key}");
+            return Ok(stored.into_response());
+        }
+
+        let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.map_err(store_failed)?;
+        let stored = StoredResponse {
+            fingerprint,
+            status: status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    let value = value.to_str().ok()?;
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect(),
+            body: body.to_vec(),
+        };
+
+        // The write has already happened by now, so a failure only costs the ability to replay it
+        let expires_at = now.saturating_add(self.ttl_seconds);
+        if let Err(e) = self.store.put(&scoped_key, &stored, expires_at) {
+            error!("Failed to store the response for idempotency key {key}: {e}");
+        }
+
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+
+    /// Waits for any request already in progress with the key to finish, then holds the key until
+    /// the returned guard is dropped. Gives up once the in flight wait has passed, rather than
+    /// holding a connection open for as long as the first request takes.
+    async fn begin(&self, key: &str) -> ServiceResult<InFlight<'_>> {
+        let lock = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        let mut in_flight = InFlight {
+            idempotency: self,
+            key: key.to_string(),
+            guard: None,
+        };
+        let guard = tokio::time::timeout(self.in_flight_wait, lock.lock_owned())
+            .await
+            .map_err(|_| ServiceError::IdempotencyKeyInFlight)?;
+        in_flight.guard = Some(guard);
+
+        Ok(in_flight)
+    }
+}
+
+struct InFlight<'a> {
+    idempotency: &'a Idempotency,
+    key: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+
+        // Nothing else is waiting on the key once the map holds the only reference to its lock
+        let mut in_flight = self.idempotency.in_flight.lock().unwrap();
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// Makes writes sent with an `Idempotency-Key` header idempotent. Writes without one are passed
+/// through untouched.
+pub async fn idempotent_writes(
+    State(state): State<Arc<RouterState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH => key.to_string(),
+        _ => {
+            return ServiceError::IdempotencyKeyInvalid(format!(
+                "The Idempotency-Key header must be between 1 and {MAX_IDEMPOTENCY_KEY_LENGTH} visible ASCII characters."
+            ))
+            .into_response()
+        }
+    };
+
+    state
+        .idempotency
+        .handle(&key, request, next)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored() -> StoredResponse {
+        StoredResponse {
+            fingerprint: "fingerprint".to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: b"{}".to_vec(),
+        }
+    }
+
+    fn assert_expires(store: &dyn IdempotencyStore) {
+        store.put("key", &stored(), 100).unwrap();
+
+        assert_eq!(store.get("key", 99).unwrap(), Some(stored()));
+        assert_eq!(store.get("key", 100).unwrap(), None);
+        assert_eq!(store.get("other", 99).unwrap(), None);
+    }
+
+    #[test]
+    fn stored_responses_expire() {
+        assert_expires(&MemoryIdempotencyStore::default());
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("idempotency.db");
+        assert_expires(&SqliteIdempotencyStore::open(path.to_str().unwrap()).unwrap());
+    }
+}
@@ -9,11 +9,21 @@
     clippy::single_char_pattern
 )]
 
+pub mod audit;
+pub mod authentication;
 pub mod config;
 pub mod errors;
+pub mod health;
+pub mod idempotency;
+pub mod metrics;
 pub mod organization;
+pub mod policy;
+pub mod redaction;
+pub mod request_context;
 pub mod requests;
 pub mod responses;
 pub mod router;
 pub mod salesforce;
+pub mod soql;
+pub mod telemetry;
 pub mod extractors;
@@ -1,29 +1,59 @@
+use std::env;
+
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use salesforce_api::config::load_salesforce_configurations;
 use salesforce_api::errors::ServiceResult;
+use salesforce_api::redaction::{self, RedactingMakeWriter};
 use salesforce_api::router::ServiceRouter;
 use salesforce_api::salesforce::resolver::SalesforceServiceResolver;
+use salesforce_api::telemetry::{self, TelemetryConfiguration};
 
 #[tokio::main]
 async fn main() -> ServiceResult<()> {
+    let tracer_provider = telemetry::tracer_provider(&TelemetryConfiguration::from_env())?;
+
+    // Set LOG_FORMAT=json to emit one JSON object per line for log aggregators
+    let json_logs = env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+    let (json_layer, text_layer) = if json_logs {
+        (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(RedactingMakeWriter::new(std::io::stdout)),
+            ),
+            None,
+        )
+    } else {
+        (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(RedactingMakeWriter::new(std::io::stdout)),
+            ),
+        )
+    };
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
                 "salesforce_api=debug,tower_http=debug,axum::rejection=trace".into()
             }),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(text_layer)
+        .with(json_layer)
+        .with(telemetry::layer(&tracer_provider))
         .init();
 
     info!("Application initialized, loading API configuration");
 
     let system_configuration = load_salesforce_configurations().await?;
+    redaction::configure(&system_configuration.service_config.redaction);
     let port = system_configuration.service_config.port;
     let salesforce_resolver = SalesforceServiceResolver::new(system_configuration);
     let port = port.unwrap_or(8080);
-    let router = ServiceRouter::new_router(salesforce_resolver);
+    let router = ServiceRouter::new_router(salesforce_resolver)?;
 
     info!(
         "Configuration successfully parsed, starting server on port {}",
@@ -38,5 +68,7 @@ async fn main() -> ServiceResult<()> {
         .await
         .expect("Failed to start API server.");
 
+    telemetry::shutdown(tracer_provider);
+
     Ok(())
 }
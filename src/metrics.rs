@@ -0,0 +1,167 @@
+use std::sync::OnceLock;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use tokio::time::Instant;
+use tracing::error;
+
+/// Buckets for outbound Salesforce calls, which run considerably longer than our own handlers.
+const SALESFORCE_LATENCY_BUCKETS: [f64; 11] =
+    [0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests_total: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    pub salesforce_requests_total: IntCounterVec,
+    pub salesforce_request_duration_seconds: HistogramVec,
+    pub token_refreshes_total: IntCounterVec,
+    pub api_usage: IntGaugeVec,
+    pub record_cache_lookups_total: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "Requests handled, by route and status.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle requests, by route and status.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let salesforce_requests_total = IntCounterVec::new(
+            Opts::new(
+                "salesforce_requests_total",
+                "Calls made to Salesforce, by organization, operation and response status.",
+            ),
+            &["organization", "operation", "status"],
+        )
+        .unwrap();
+        let salesforce_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "salesforce_request_duration_seconds",
+                "Time taken by calls to Salesforce, by organization and operation.",
+            )
+            .buckets(SALESFORCE_LATENCY_BUCKETS.to_vec()),
+            &["organization", "operation"],
+        )
+        .unwrap();
+        let token_refreshes_total = IntCounterVec::new(
+            Opts::new(
+                "salesforce_token_refreshes_total",
+                "Access tokens requested from Salesforce, by organization and outcome.",
+            ),
+            &["organization", "outcome"],
+        )
+        .unwrap();
+        let api_usage = IntGaugeVec::new(
+            Opts::new(
+                "salesforce_api_usage",
+                "Daily API requests used and allowed, as last reported by Salesforce.",
+            ),
+            &["organization", "kind"],
+        )
+        .unwrap();
+        let record_cache_lookups_total = IntCounterVec::new(
+            Opts::new(
+                "salesforce_record_cache_lookups_total",
+                "Reads of cached objects, by organization and whether the cache had the record.",
+            ),
+            &["organization", "outcome"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(http_requests_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_request_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(salesforce_requests_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(salesforce_request_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(token_refreshes_total.clone()))
+            .unwrap();
+        registry.register(Box::new(api_usage.clone())).unwrap();
+        registry
+            .register(Box::new(record_cache_lookups_total.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            salesforce_requests_total,
+            salesforce_request_duration_seconds,
+            token_refreshes_total,
+            api_usage,
+            record_cache_lookups_total,
+        }
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> Response {
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+
+        if let Err(e) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {e}");
+        }
+
+        ([(CONTENT_TYPE, encoder.format_type().to_string())], buffer).into_response()
+    }
+}
+
+/// Metrics shared across the service, registered on first use.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Records the count and latency of every routed request, labeled by the route template rather
+/// than the raw path so record ids don't explode the number of series.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let started_at = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+
+    metrics()
+        .http_requests_total
+        .with_label_values(&labels)
+        .inc();
+    metrics()
+        .http_request_duration_seconds
+        .with_label_values(&labels)
+        .observe(started_at.elapsed().as_secs_f64());
+
+    response
+}
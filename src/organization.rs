@@ -1,16 +1,36 @@
 use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 
 use axum::http::HeaderValue;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::ServiceError;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SalesforceOrganization {
     NationalFunding,
     QuickBridge,
     Underwriting,
 }
 
+impl SalesforceOrganization {
+    pub const ALL: [Self; 3] = [Self::NationalFunding, Self::QuickBridge, Self::Underwriting];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NationalFunding => "NationalFunding",
+            Self::QuickBridge => "QuickBridge",
+            Self::Underwriting => "Underwriting",
+        }
+    }
+}
+
+impl Display for SalesforceOrganization {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl TryFrom<&HeaderValue> for SalesforceOrganization {
     type Error = ServiceError;
 
@@ -0,0 +1,178 @@
+//! Declarative authorization policy restricting what each caller may do, loaded from a JSON file
+//! that grants callers operations on objects and fields within organizations, e.g.
+//!
+//! ```json
+//! {
+//!     "Callers": {
+//!         "underwriting-ui": [
+//!             {
+//!                 "Organizations": ["Underwriting"],
+//!                 "Objects": ["Loan__c"],
+//!                 "Operations": ["Read", "Query"],
+//!                 "Fields": ["Id", "Name", "Amount__c"]
+//!             }
+//!         ]
+//!     }
+//! }
+//! ```
+//!
+//! Anything not granted is denied, including every request from callers the policy doesn't list.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::authentication::Caller;
+use crate::errors::{ServiceError, ServiceResult};
+use crate::organization::SalesforceOrganization;
+
+/// Grants matching any object use this in place of a name.
+const ANY_OBJECT: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PolicyOperation {
+    Read,
+    Query,
+    Create,
+    Update,
+    Delete,
+}
+
+impl Display for PolicyOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let operation = match self {
+            Self::Read => "read",
+            Self::Query => "query",
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        };
+
+        f.write_str(operation)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Grant {
+    pub organizations: Vec<SalesforceOrganization>,
+    /// sObject names the grant applies to, or `*` for all of them.
+    pub objects: Vec<String>,
+    pub operations: Vec<PolicyOperation>,
+    /// Fields that may be read or written, or every field when omitted.
+    pub fields: Option<Vec<String>>,
+}
+
+impl Grant {
+    fn applies_to(
+        &self,
+        organization: SalesforceOrganization,
+        object: &str,
+        operation: PolicyOperation,
+    ) -> bool {
+        self.organizations.contains(&organization)
+            && self.operations.contains(&operation)
+            && self
+                .objects
+                .iter()
+                .any(|granted| granted == ANY_OBJECT || granted.eq_ignore_ascii_case(object))
+    }
+}
+
+/// The fields a caller may access on an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldAccess {
+    All,
+    /// Lowercased names of the accessible fields.
+    Only(HashSet<String>),
+}
+
+impl FieldAccess {
+    pub fn allows(&self, field: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(fields) => fields.contains(&field.to_lowercase()),
+        }
+    }
+
+    /// The given fields that are not accessible.
+    pub fn forbidden<'a>(&self, fields: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        fields
+            .into_iter()
+            .filter(|field| !self.allows(field))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Removes inaccessible fields from a record, keeping the `attributes` Salesforce describes it with.
+    pub fn retain_accessible(&self, record: &mut Value) {
+        if let (Self::Only(_), Value::Object(fields)) = (self, record) {
+            fields.retain(|name, _| name == "attributes" || self.allows(name));
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Only(mut fields), Self::Only(other_fields)) => {
+                fields.extend(other_fields);
+                Self::Only(fields)
+            }
+            _ => Self::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AuthorizationPolicy {
+    #[serde(default)]
+    callers: HashMap<String, Vec<Grant>>,
+}
+
+impl AuthorizationPolicy {
+    pub fn load(path: &str) -> ServiceResult<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            ServiceError::AuthenticationConfigurationInvalid(format!(
+                "Policy file {path} could not be read: {e}"
+            ))
+        })?;
+
+        serde_json::from_str(&contents).map_err(|e| {
+            ServiceError::AuthenticationConfigurationInvalid(format!(
+                "Policy file {path} is not valid: {e}"
+            ))
+        })
+    }
+
+    /// Ensures the caller has been granted the operation on the object, returning the fields they
+    /// may access across every grant that applies.
+    pub fn authorize(
+        &self,
+        caller: &Caller,
+        organization: SalesforceOrganization,
+        object: &str,
+        operation: PolicyOperation,
+    ) -> ServiceResult<FieldAccess> {
+        self.callers
+            .get(&caller.id)
+            .into_iter()
+            .flatten()
+            .filter(|grant| grant.applies_to(organization, object, operation))
+            .map(|grant| match &grant.fields {
+                None => FieldAccess::All,
+                Some(fields) => {
+                    FieldAccess::Only(fields.iter().map(|field| field.to_lowercase()).collect())
+                }
+            })
+            .reduce(FieldAccess::merge)
+            .ok_or_else(|| {
+                ServiceError::Forbidden(format!(
+                    "{} is not permitted to {operation} {object} in {organization}.",
+                    caller.id
+                ))
+            })
+    }
+}
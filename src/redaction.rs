@@ -0,0 +1,242 @@
+//! Masking of sensitive values before they reach logs. Records carry SSNs, bank accounts and tax
+//! ids, so payloads and SOQL are redacted where they're logged and every formatted log line is
+//! passed through the same patterns on its way out as a last line of defense.
+
+use std::io::{self, Write};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Replacement for every redacted value.
+pub const REDACTED: &str = "***";
+
+/// Field names containing any of these, ignoring case and punctuation, always have their values
+/// masked, e.g. `SSN__c`, `Bank_Account_Number__c` or `consumerSecret`.
+const DEFAULT_SENSITIVE_FIELDS: [&str; 12] = [
+    "ssn",
+    "socialsecurity",
+    "taxid",
+    "taxpayerid",
+    "bankaccount",
+    "accountnumber",
+    "routingnumber",
+    "password",
+    "secret",
+    "token",
+    "authorization",
+    "apikey",
+];
+
+/// Values shaped like SSNs and employer tax ids are masked wherever they appear.
+const DEFAULT_SENSITIVE_PATTERNS: [&str; 2] = [r"\b\d{3}-\d{2}-\d{4}\b", r"\b\d{2}-\d{7}\b"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RedactionConfiguration {
+    /// Additional field name fragments whose values are masked, on top of the defaults.
+    pub sensitive_fields: Vec<String>,
+    /// Additional regular expressions whose matches are masked, on top of the defaults.
+    pub sensitive_patterns: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Redactor {
+    sensitive_fields: Vec<String>,
+    patterns: Vec<Regex>,
+    assignment: Regex,
+    soql_comparison: Regex,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfiguration) -> Self {
+        let sensitive_fields = DEFAULT_SENSITIVE_FIELDS
+            .iter()
+            .map(|field| field.to_string())
+            .chain(config.sensitive_fields.iter().map(|field| normalize(field)))
+            .filter(|field| !field.is_empty())
+            .collect();
+
+        let patterns = DEFAULT_SENSITIVE_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .chain(config.sensitive_patterns.iter().cloned())
+            .filter_map(|pattern| match Regex::new(&pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Ignoring invalid redaction pattern: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            sensitive_fields,
+            patterns,
+            // `name=value`, `name: value` and `"name":"value"` pairs as they appear in formatted logs,
+            // including SOQL comparisons that were already redacted at the source
+            assignment: Regex::new(
+                r#"(?i)("?)([A-Za-z_][\w.]*)("?\s*[=:]\s*)("(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|[^\s,;{}\[\]"'][^\s,;}\]]*)"#,
+            )
+            .unwrap(),
+            // Comparisons in SOQL filters, with a quoted, parenthesized or bare literal
+            soql_comparison: Regex::new(
+                r"(?i)\b([A-Za-z_][\w.]*)(\s*(?:=|!=|<>|<=|>=|<|>|\s+LIKE\s+|\s+NOT\s+IN\s+|\s+IN\s+|\s+INCLUDES\s+|\s+EXCLUDES\s+)\s*)('(?:[^'\\]|\\.)*'|\([^)]*\)|[^\s)]+)",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Determines if a field's value should never be logged based on its name.
+    pub fn is_sensitive_field(&self, name: &str) -> bool {
+        let name = normalize(name);
+        self.sensitive_fields
+            .iter()
+            .any(|field| name.contains(field.as_str()))
+    }
+
+    /// Masks any values matching the sensitive patterns.
+    pub fn redact_text(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern.replace_all(&text, REDACTED).into_owned()
+            })
+    }
+
+    /// Masks the values of sensitive fields, recursing into nested records, along with any string
+    /// values matching the sensitive patterns.
+    pub fn redact_value(&self, value: &Value) -> Value {
+        match value {
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = if self.is_sensitive_field(name) && !value.is_null() {
+                            Value::String(REDACTED.to_string())
+                        } else {
+                            self.redact_value(value)
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect(),
+            ),
+            Value::Array(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.redact_value(value))
+                    .collect(),
+            ),
+            Value::String(text) => Value::String(self.redact_text(text)),
+            _ => value.clone(),
+        }
+    }
+
+    /// Masks the literals compared against sensitive fields in a SOQL query, along with any
+    /// literals matching the sensitive patterns.
+    pub fn redact_soql(&self, soql: &str) -> String {
+        let redacted = self
+            .soql_comparison
+            .replace_all(soql, |captures: &Captures| {
+                if self.is_sensitive_field(&captures[1]) {
+                    let literal = if captures[3].starts_with('(') {
+                        format!("('{REDACTED}')")
+                    } else {
+                        format!("'{REDACTED}'")
+                    };
+                    format!("{}{}{literal}", &captures[1], &captures[2])
+                } else {
+                    captures[0].to_string()
+                }
+            });
+
+        self.redact_text(&redacted)
+    }
+
+    /// Masks sensitive fields and patterns within an already formatted log line.
+    pub fn redact_log_line(&self, line: &str) -> String {
+        let redacted = self.assignment.replace_all(line, |captures: &Captures| {
+            if self.is_sensitive_field(&captures[2]) {
+                let quote = match captures[4].chars().next() {
+                    Some(quote @ ('"' | '\'')) => quote.to_string(),
+                    _ => String::new(),
+                };
+                format!(
+                    "{}{}{}{quote}{REDACTED}{quote}",
+                    &captures[1], &captures[2], &captures[3]
+                )
+            } else {
+                captures[0].to_string()
+            }
+        });
+
+        self.redact_text(&redacted)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn redactor_lock() -> &'static RwLock<Arc<Redactor>> {
+    static REDACTOR: OnceLock<RwLock<Arc<Redactor>>> = OnceLock::new();
+    REDACTOR.get_or_init(|| RwLock::new(Arc::new(Redactor::new(&Default::default()))))
+}
+
+/// The redactor in use, which applies the default fields and patterns until [`configure`] is called.
+pub fn redactor() -> Arc<Redactor> {
+    redactor_lock().read().unwrap().clone()
+}
+
+/// Replaces the redactor once service configuration has loaded. Logging starts before then, so
+/// the defaults apply to anything logged during startup.
+pub fn configure(config: &RedactionConfiguration) {
+    *redactor_lock().write().unwrap() = Arc::new(Redactor::new(config));
+}
+
+/// Wraps a log writer, redacting each formatted line before it's written.
+#[derive(Debug, Clone, Copy)]
+pub struct RedactingMakeWriter<M> {
+    inner: M,
+}
+
+impl<M> RedactingMakeWriter<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RedactingWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    // Formatters write each event in a single call, so every write holds complete lines
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf);
+        self.inner
+            .write_all(redactor().redact_log_line(&line).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,112 @@
+//! Per-request context shared with everything that runs on behalf of a request, used to correlate
+//! log lines, error responses and Salesforce calls with the request that caused them.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tokio::time::Instant;
+use tracing::info;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Caller supplied request ids longer than this are replaced rather than echoed into every log line.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+#[derive(Debug)]
+pub struct RequestContext {
+    pub request_id: String,
+    salesforce_calls: AtomicU32,
+}
+
+impl RequestContext {
+    fn new(request_id: String) -> Self {
+        Self {
+            request_id,
+            salesforce_calls: AtomicU32::new(0),
+        }
+    }
+
+    pub fn salesforce_calls(&self) -> u32 {
+        self.salesforce_calls.load(Ordering::Relaxed)
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: Arc<RequestContext>;
+}
+
+/// The id of the request currently being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_CONTEXT
+        .try_with(|context| context.request_id.clone())
+        .ok()
+}
+
+/// Counts a call made to Salesforce against the request currently being handled.
+pub fn record_salesforce_call() {
+    let _ = REQUEST_CONTEXT.try_with(|context| {
+        context.salesforce_calls.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// Accepts the caller's `X-Request-Id` or generates one, makes it available for the rest of the
+/// request, echoes it on the response and writes an access log line once the request completes.
+pub async fn track_request_context(mut request: Request, next: Next) -> Response {
+    let started_at = Instant::now();
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let organization = request
+        .headers()
+        .get("SF-Organization")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let context = Arc::new(RequestContext::new(request_id.clone()));
+    request.extensions_mut().insert(context.clone());
+
+    let mut response = REQUEST_CONTEXT
+        .scope(context.clone(), next.run(request))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    info!(
+        target: "salesforce_api::access_log",
+        request_id,
+        method,
+        route,
+        organization,
+        status = response.status().as_u16(),
+        latency_ms = started_at.elapsed().as_millis() as u64,
+        salesforce_calls = context.salesforce_calls(),
+        "Request completed"
+    );
+
+    response
+}
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LENGTH
+        && value.chars().all(|c| c.is_ascii_graphic())
+}
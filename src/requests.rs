@@ -2,6 +2,53 @@ use serde::Deserialize;
 use serde_json::Value;
 use validator::Validate;
 
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateObjectParameters {
+    /// Reports the previous and new value of each field the update changed.
+    #[serde(default)]
+    pub capture_changes: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FindObjectParameters {
+    /// Comma separated fields to retrieve instead of every field on the object.
+    pub fields: Option<String>,
+}
+
+impl FindObjectParameters {
+    pub fn fields(&self) -> Option<Vec<String>> {
+        split_fields(self.fields.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedRecordsParameters {
+    /// Comma separated fields to retrieve instead of every field on the related object.
+    pub fields: Option<String>,
+    /// Continues a list of child records from the `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+impl RelatedRecordsParameters {
+    pub fn fields(&self) -> Option<Vec<String>> {
+        split_fields(self.fields.as_deref())
+    }
+}
+
+fn split_fields(fields: Option<&str>) -> Option<Vec<String>> {
+    let fields: Vec<String> = fields?
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    (!fields.is_empty()).then_some(fields)
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateObjectRecordRequest {
     #[validate(required)]
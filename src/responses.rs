@@ -1,18 +1,40 @@
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use axum::http::HeaderValue;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::ServiceResult;
+use crate::salesforce::bulk::{
+    BulkQueryResultChunk, BulkResultFormat, SFORCE_LOCATOR_HEADER, SFORCE_NUMBER_OF_RECORDS_HEADER,
+};
+use crate::salesforce::changes::FieldChange;
+use crate::salesforce::concurrency::VersionedRecord;
 
 #[derive(Debug, Serialize)]
 pub struct TransactionSuccessfulResponse {
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes: Option<Vec<FieldChange>>,
     #[serde(skip_serializing)]
     status: StatusCode,
 }
 
 impl TransactionSuccessfulResponse {
     pub fn new(message: String, status: StatusCode) -> Self {
-        Self { message, status }
+        Self {
+            message,
+            changes: None,
+            status,
+        }
+    }
+
+    /// Includes the field level changes the transaction made in the response.
+    pub fn with_changes(mut self, changes: Vec<FieldChange>) -> Self {
+        self.changes = Some(changes);
+        self
     }
 }
 
@@ -21,3 +43,86 @@ impl IntoResponse for TransactionSuccessfulResponse {
         (self.status, Json(self)).into_response()
     }
 }
+
+/// A single record, with its version exposed as `ETag` and `Last-Modified` headers so callers can
+/// make conditional updates. Records of cached objects may be reused by the caller for as long as
+/// this service would, others have to be revalidated.
+#[derive(Debug)]
+pub struct RecordResponse(pub VersionedRecord);
+
+impl IntoResponse for RecordResponse {
+    fn into_response(self) -> Response {
+        let VersionedRecord {
+            record,
+            version,
+            max_age,
+        } = self.0;
+        let mut response = (StatusCode::OK, Json(record)).into_response();
+        let headers = response.headers_mut();
+
+        if let Some(Ok(etag)) = version.etag.map(HeaderValue::try_from) {
+            headers.insert(ETAG, etag);
+        }
+
+        if let Some(Ok(last_modified)) = version.last_modified.map(HeaderValue::try_from) {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+
+        // Private as fields are filtered by what each caller is allowed to read
+        let cache_control = match max_age {
+            Some(max_age) => format!("private, max-age={}", max_age.as_secs()),
+            None => "private, no-cache".to_string(),
+        };
+        if let Ok(cache_control) = HeaderValue::try_from(cache_control) {
+            headers.insert(CACHE_CONTROL, cache_control);
+        }
+
+        response
+    }
+}
+
+#[derive(Debug)]
+pub struct BulkQueryResultsResponse {
+    chunk: BulkQueryResultChunk,
+    records: Option<Vec<Value>>,
+}
+
+impl BulkQueryResultsResponse {
+    /// Prepares the chunk in the requested format, parsing the CSV up front when JSON is requested
+    /// so conversion failures surface as errors rather than a partial response.
+    pub fn new(chunk: BulkQueryResultChunk, format: BulkResultFormat) -> ServiceResult<Self> {
+        let records = match format {
+            BulkResultFormat::Csv => None,
+            BulkResultFormat::Json => Some(chunk.records()?),
+        };
+
+        Ok(Self { chunk, records })
+    }
+}
+
+impl IntoResponse for BulkQueryResultsResponse {
+    fn into_response(self) -> Response {
+        let chunk = self.chunk;
+        let mut response = match self.records {
+            None => (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "text/csv; charset=utf-8")],
+                chunk.csv,
+            )
+                .into_response(),
+            Some(records) => (StatusCode::OK, Json(records)).into_response(),
+        };
+
+        let headers = response.headers_mut();
+
+        if let Some(Ok(locator)) = chunk.locator.map(|locator| locator.parse()) {
+            headers.insert(SFORCE_LOCATOR_HEADER, locator);
+        }
+
+        if let Some(number_of_records) = chunk.number_of_records {
+            headers.insert(SFORCE_NUMBER_OF_RECORDS_HEADER, number_of_records.into());
+        }
+
+        response
+    }
+}
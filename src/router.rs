@@ -1,70 +1,228 @@
 use std::sync::Arc;
 
-use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware;
+use axum::response::Response;
 use axum::routing::{get, post, put};
 use axum::{Json, Router};
-use serde_json::Value;
+use serde_json::{json, Map, Value};
 use tracing::info;
 
-use crate::errors::ServiceResult;
+use crate::audit::{AuditEntry, AuditLog, AuditOperation};
+use crate::authentication::{authenticate_requests, Authentication, Caller};
+use crate::errors::{ServiceError, ServiceResult};
+use crate::extractors::authenticated_caller::AuthenticatedCaller;
+use crate::extractors::authorize::{
+    authorize, Authorized, AuthorizedJson, AuthorizedSoql, ReadAction, UpdateAction,
+};
 use crate::extractors::extract_org::ExtractSalesforceOrg;
+use crate::extractors::preconditions::ExtractPreconditions;
 use crate::extractors::resolve_service::ResolveSalesforceServiceFromService;
-use crate::extractors::validation::ValidatedJson;
-use crate::requests::CreateObjectRecordRequest;
-use crate::responses::TransactionSuccessfulResponse;
+use crate::extractors::validation::{validate_path_parameters, ValidatedJson};
+use crate::health::{ReadinessCheck, ReadinessReport};
+use crate::idempotency::{idempotent_writes, Idempotency};
+use crate::metrics::{metrics, track_requests};
+use crate::organization::SalesforceOrganization;
+use crate::policy::{AuthorizationPolicy, FieldAccess, PolicyOperation};
+use crate::request_context::track_request_context;
+use crate::requests::{
+    CreateObjectRecordRequest, FindObjectParameters, RelatedRecordsParameters,
+    UpdateObjectParameters,
+};
+use crate::responses::{BulkQueryResultsResponse, RecordResponse, TransactionSuccessfulResponse};
+use crate::salesforce::bulk::{
+    BulkQueryJob, BulkQueryJobOwner, BulkQueryJobOwners, BulkQueryResultsParameters,
+};
+use crate::salesforce::describe::{GlobalDescribe, SObjectDescribe};
 use crate::salesforce::resolver::SalesforceServiceResolver;
+use crate::salesforce::service::{RelatedRecords, UpdateOptions};
+use crate::telemetry::trace_requests;
 
 #[derive(Debug)]
 pub struct RouterState {
     pub resolver: SalesforceServiceResolver,
+    pub readiness: ReadinessCheck,
+    pub authentication: Authentication,
+    /// Operations each caller is permitted, or `None` to permit authenticated callers everything.
+    pub policy: Option<AuthorizationPolicy>,
+    pub audit: AuditLog,
+    pub idempotency: Idempotency,
+    pub bulk_jobs: BulkQueryJobOwners,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct ServiceRouter;
 
 impl ServiceRouter {
-    pub fn new_router(resolver: SalesforceServiceResolver) -> Router {
-        let state = RouterState { resolver };
+    pub fn new_router(resolver: SalesforceServiceResolver) -> ServiceResult<Router> {
+        let readiness = ReadinessCheck::new(resolver.service_configuration());
+        let authentication = Authentication::new(&resolver.service_configuration().authentication)?;
+        let policy = resolver
+            .service_configuration()
+            .policy_path
+            .as_deref()
+            .map(AuthorizationPolicy::load)
+            .transpose()?;
+        let audit = AuditLog::new(&resolver.service_configuration().audit)?;
+        let idempotency = Idempotency::new(&resolver.service_configuration().idempotency)?;
+        let state = Arc::new(RouterState {
+            resolver,
+            readiness,
+            authentication,
+            policy,
+            audit,
+            idempotency,
+            bulk_jobs: BulkQueryJobOwners::default(),
+        });
+        let idempotent = || middleware::from_fn_with_state(state.clone(), idempotent_writes);
 
-        Router::new()
+        let protected = Router::new()
             .route("/objects/:name/:id", get(find))
-            .route("/objects/:name/:id", put(update))
+            .route("/objects/:name/:id", put(update).layer(idempotent()))
+            .route("/objects/:name/:id/:relationship", get(find_related))
             .route("/objects/query", post(query))
-            .route("/objects", post(create))
-            .with_state(Arc::new(state))
+            .route("/objects", post(create).layer(idempotent()))
+            .route("/objects", get(describe_global))
+            .route("/objects/:name/describe", get(describe))
+            .route("/limits", get(limits))
+            .route("/limits/:organization", get(organization_limits))
+            .route("/jobs/query", post(create_bulk_query))
+            .route("/jobs/query/:id", get(find_bulk_query))
+            .route("/jobs/query/:id/results", get(bulk_query_results))
+            .route_layer(middleware::from_fn(validate_path_parameters))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                authenticate_requests,
+            ));
+
+        // Probes and scrapers don't carry caller credentials
+        let public = Router::new()
+            .route("/health/live", get(live))
+            .route("/health/ready", get(ready))
+            .route("/health/circuits", get(circuits))
+            .route("/metrics", get(render_metrics));
+
+        let router = protected
+            .merge(public)
+            // Applied per route so the matched route template is available to label requests by
+            .route_layer(middleware::from_fn(track_requests))
+            .route_layer(middleware::from_fn(trace_requests))
+            .route_layer(middleware::from_fn(track_request_context))
+            .with_state(state);
+
+        Ok(router)
     }
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(service, authorized))]
 async fn find(
     ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
     ExtractSalesforceOrg(org): ExtractSalesforceOrg,
+    authorized: Authorized<ReadAction>,
     Path((name, id)): Path<(String, String)>,
-) -> ServiceResult<Json<Value>> {
+    Query(parameters): Query<FindObjectParameters>,
+) -> ServiceResult<RecordResponse> {
     info!("Received request to find object {name} by id {id}");
 
-    let object = service.get_object_by_id(name, id.clone()).await?;
+    let mut object = service
+        .get_versioned_object(name, id.clone(), parameters.fields())
+        .await?;
+    authorized.fields.retain_accessible(&mut object.record);
+
+    Ok(RecordResponse(object))
+}
+
+#[tracing::instrument(skip(state, service, caller, headers, _authorized))]
+async fn find_related(
+    State(state): State<Arc<RouterState>>,
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    headers: HeaderMap,
+    _authorized: Authorized<ReadAction>,
+    Path((name, id, relationship)): Path<(String, String, String)>,
+    Query(parameters): Query<RelatedRecordsParameters>,
+) -> ServiceResult<Json<Value>> {
+    info!("Received request to find {relationship} related to object {name} {id}");
+
+    // Nothing is read from the related object until the caller is known to be allowed to, and
+    // polymorphic lookups are checked again once the referenced record's object is known
+    let related_objects = service.related_objects(&name, &relationship).await?;
+    let mut denials: Vec<ServiceError> = related_objects
+        .iter()
+        .filter_map(|object| {
+            authorize(
+                Some(&caller),
+                &headers,
+                &state,
+                object,
+                PolicyOperation::Read,
+            )
+            .err()
+        })
+        .collect();
+    if denials.len() == related_objects.len() && !denials.is_empty() {
+        return Err(denials.remove(0));
+    }
+
+    let fields = parameters.fields();
+    let related = service
+        .get_related_records(name, id, relationship, fields, parameters.cursor)
+        .await?;
+    let access = authorize(
+        Some(&caller),
+        &headers,
+        &state,
+        related.object(),
+        PolicyOperation::Read,
+    )?;
+
+    let related = match related {
+        RelatedRecords::Parent { mut record, .. } => {
+            access.retain_accessible(&mut record);
+            record
+        }
+        RelatedRecords::Children {
+            mut page,
+            next_cursor,
+            ..
+        } => {
+            retain_accessible_records(&access, &mut page);
+            if let (Some(page), Some(next_cursor)) = (page.as_object_mut(), next_cursor) {
+                page.insert("nextCursor".to_string(), Value::String(next_cursor));
+            }
+            page
+        }
+    };
 
-    Ok(Json(object))
+    Ok(Json(related))
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(state, service, query))]
 async fn query(
+    State(state): State<Arc<RouterState>>,
     ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
-    soql: String,
+    query: AuthorizedSoql,
 ) -> ServiceResult<Json<Value>> {
     info!("Received request for SOQL query");
 
-    let objects = service.get_objects(soql).await?;
+    state
+        .resolver
+        .service_configuration()
+        .query_guardrails
+        .check(&query.query)?;
+    service.check_query_field_policies(&query.query).await?;
+
+    let mut objects = service.get_objects(query.soql).await?;
+    retain_accessible_records(&query.fields, &mut objects);
 
     Ok(Json(objects))
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(_service, _request))]
 async fn create(
-    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
-    ValidatedJson(request): ValidatedJson<CreateObjectRecordRequest>,
+    ResolveSalesforceServiceFromService(_service): ResolveSalesforceServiceFromService,
+    ValidatedJson(_request): ValidatedJson<CreateObjectRecordRequest>,
 ) -> ServiceResult<TransactionSuccessfulResponse> {
     info!("Received request for query, executing...");
     Ok(TransactionSuccessfulResponse::new(
@@ -73,18 +231,266 @@ async fn create(
     ))
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip(state, service, caller, request))]
 async fn update(
+    State(state): State<Arc<RouterState>>,
     ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
     Path((name, id)): Path<(String, String)>,
-    Json(request): Json<Value>,
+    Query(parameters): Query<UpdateObjectParameters>,
+    ExtractPreconditions(preconditions): ExtractPreconditions,
+    request: AuthorizedJson<UpdateAction>,
 ) -> ServiceResult<TransactionSuccessfulResponse> {
     info!("Received request for updating object");
 
-    service.update_object(name, id, request).await?;
+    let mut entry = AuditEntry::new(
+        &caller,
+        service.organization(),
+        AuditOperation::Update,
+        &name,
+        Some(&id),
+        &request.payload,
+    );
 
-    Ok(TransactionSuccessfulResponse::new(
+    let options = UpdateOptions {
+        preconditions,
+        capture_changes: parameters.capture_changes,
+    };
+    let result = service
+        .update_object_with_options(name, id, request.payload, options)
+        .await;
+
+    if let Ok(Some(changes)) = &result {
+        entry = entry.with_diff(changes);
+    }
+    state.audit.record(entry.with_result(&result)).await;
+
+    let response = TransactionSuccessfulResponse::new(
         "Record successfully updated.".to_string(),
         StatusCode::OK,
-    ))
+    );
+
+    Ok(match result? {
+        Some(changes) => response.with_changes(changes),
+        None => response,
+    })
+}
+
+#[tracing::instrument(skip(service, _authorized))]
+async fn describe(
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    _authorized: Authorized<ReadAction>,
+    Path(name): Path<String>,
+) -> ServiceResult<Json<Arc<SObjectDescribe>>> {
+    info!("Received request to describe object {name}");
+
+    let describe = service.describe_object(name).await?;
+
+    Ok(Json(describe))
+}
+
+#[tracing::instrument(skip(state, service, caller, headers))]
+async fn describe_global(
+    State(state): State<Arc<RouterState>>,
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    headers: HeaderMap,
+) -> ServiceResult<Json<Arc<GlobalDescribe>>> {
+    info!("Received request to describe all objects");
+
+    let describe = service.describe_global().await?;
+
+    if state.policy.is_none() {
+        return Ok(Json(describe));
+    }
+
+    // Only objects the caller may read or query are listed
+    let mut describe = GlobalDescribe::clone(&describe);
+    describe.sobjects.retain(|sobject| {
+        [PolicyOperation::Read, PolicyOperation::Query]
+            .into_iter()
+            .any(|operation| {
+                authorize(Some(&caller), &headers, &state, &sobject.name, operation).is_ok()
+            })
+    });
+
+    Ok(Json(Arc::new(describe)))
+}
+
+#[tracing::instrument(skip(state, service, caller, query))]
+async fn create_bulk_query(
+    State(state): State<Arc<RouterState>>,
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    query: AuthorizedSoql,
+) -> ServiceResult<(StatusCode, Json<BulkQueryJob>)> {
+    info!("Received request for bulk SOQL query");
+
+    service.check_query_field_policies(&query.query).await?;
+
+    let job = service.create_bulk_query_job(query.soql).await?;
+    state.bulk_jobs.record(
+        service.organization(),
+        &job.id,
+        &caller.id,
+        &query.query.object,
+    );
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[tracing::instrument(skip(state, service, caller, headers))]
+async fn find_bulk_query(
+    State(state): State<Arc<RouterState>>,
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> ServiceResult<Json<BulkQueryJob>> {
+    info!("Received request to poll bulk query job {id}");
+
+    authorize_bulk_job(&state, &caller, &headers, service.organization(), &id)?;
+
+    let job = service.get_bulk_query_job(id).await?;
+
+    Ok(Json(job))
+}
+
+#[tracing::instrument(skip(state, service, caller, headers))]
+async fn bulk_query_results(
+    State(state): State<Arc<RouterState>>,
+    ResolveSalesforceServiceFromService(service): ResolveSalesforceServiceFromService,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(parameters): Query<BulkQueryResultsParameters>,
+) -> ServiceResult<BulkQueryResultsResponse> {
+    info!("Received request for bulk query job {id} results");
+
+    let (owner, fields) =
+        authorize_bulk_job(&state, &caller, &headers, service.organization(), &id)?;
+
+    let mut chunk = service
+        .get_bulk_query_results(id, parameters.locator, parameters.max_records)
+        .await?;
+    service
+        .restrict_bulk_query_results(&owner.object, &mut chunk, |column| {
+            let relationship = column.split('.').next().unwrap_or(column);
+            fields.allows(column) || fields.allows(relationship)
+        })
+        .await?;
+
+    BulkQueryResultsResponse::new(chunk, parameters.format)
+}
+
+async fn live() -> Json<Value> {
+    Json(json!({ "status": "live" }))
+}
+
+#[tracing::instrument(skip(state))]
+async fn ready(State(state): State<Arc<RouterState>>) -> (StatusCode, Json<ReadinessReport>) {
+    let report = state.readiness.check(&state.resolver).await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
+
+#[tracing::instrument(skip(state))]
+async fn circuits(State(state): State<Arc<RouterState>>) -> Json<Value> {
+    let circuits: Map<String, Value> = state
+        .resolver
+        .services()
+        .into_iter()
+        .map(|(organization, service)| (organization.to_string(), json!(service.circuit_state())))
+        .collect();
+
+    Json(Value::Object(circuits))
+}
+
+#[tracing::instrument(skip(state, caller))]
+async fn limits(
+    State(state): State<Arc<RouterState>>,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+) -> Json<Value> {
+    let limits: Map<String, Value> = state
+        .resolver
+        .services()
+        .into_iter()
+        .filter(|(organization, _)| caller.authorize_organization(*organization).is_ok())
+        .map(|(organization, service)| {
+            (
+                organization.to_string(),
+                json!({ "apiUsage": service.api_usage() }),
+            )
+        })
+        .collect();
+
+    Json(Value::Object(limits))
+}
+
+#[tracing::instrument(skip(state, caller))]
+async fn organization_limits(
+    State(state): State<Arc<RouterState>>,
+    AuthenticatedCaller(caller): AuthenticatedCaller,
+    Path(organization): Path<SalesforceOrganization>,
+) -> ServiceResult<Json<Value>> {
+    info!("Received request for {organization} limits");
+
+    caller.authorize_organization(organization)?;
+
+    let service = state.resolver.resolve(organization);
+    let limits = service.get_limits().await?;
+
+    Ok(Json(json!({
+        "apiUsage": service.api_usage(),
+        "limits": limits,
+    })))
+}
+
+async fn render_metrics() -> Response {
+    metrics().render()
+}
+
+/// Ensures the caller created the bulk query job and may still query the object it queries,
+/// returning the job's owner and the fields they may access on the object.
+fn authorize_bulk_job(
+    state: &Arc<RouterState>,
+    caller: &Caller,
+    headers: &HeaderMap,
+    organization: SalesforceOrganization,
+    id: &str,
+) -> ServiceResult<(BulkQueryJobOwner, FieldAccess)> {
+    let owner = state
+        .bulk_jobs
+        .owner(organization, id)
+        .filter(|owner| owner.caller == caller.id)
+        .ok_or_else(|| {
+            ServiceError::Forbidden(format!(
+                "{} is not permitted to access bulk query job {id}.",
+                caller.id
+            ))
+        })?;
+
+    let fields = authorize(
+        Some(caller),
+        headers,
+        state,
+        &owner.object,
+        PolicyOperation::Query,
+    )?;
+
+    Ok((owner, fields))
+}
+
+/// Removes inaccessible fields from each record in a query result.
+fn retain_accessible_records(fields: &FieldAccess, result: &mut Value) {
+    if let Some(Value::Array(records)) = result.get_mut("records") {
+        records
+            .iter_mut()
+            .for_each(|record| fields.retain_accessible(record));
+    }
 }
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::errors::ServiceResult;
+use crate::organization::SalesforceOrganization;
+use crate::salesforce::conversion::csv_to_records;
+
+/// Header Salesforce uses to hand back the cursor for the next chunk of bulk query results.
+pub const SFORCE_LOCATOR_HEADER: &str = "Sforce-Locator";
+
+/// Header Salesforce uses to report how many records are contained in a bulk results chunk.
+pub const SFORCE_NUMBER_OF_RECORDS_HEADER: &str = "Sforce-NumberOfRecords";
+
+/// Salesforce deletes bulk query jobs a week after they're created, so ownership isn't kept longer.
+const BULK_JOB_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Lifecycle states of a Bulk API 2.0 query job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulkJobState {
+    UploadComplete,
+    InProgress,
+    Aborted,
+    JobComplete,
+    Failed,
+}
+
+impl BulkJobState {
+    /// Determines if the job has reached a state it will never leave.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Aborted | Self::JobComplete | Self::Failed)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateBulkQueryJobRequest<'a> {
+    pub operation: &'a str,
+    pub query: &'a str,
+}
+
+/// Job information returned by Salesforce when creating or polling a bulk query job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkQueryJob {
+    pub id: String,
+    pub operation: String,
+    pub object: String,
+    pub state: BulkJobState,
+    pub created_date: Option<String>,
+    pub system_modstamp: Option<String>,
+    pub number_records_processed: Option<u64>,
+    pub retries: Option<u32>,
+    pub total_processing_time: Option<u64>,
+    pub error_message: Option<String>,
+}
+
+/// A single chunk of CSV results for a completed bulk query job.
+#[derive(Debug, Clone)]
+pub struct BulkQueryResultChunk {
+    pub csv: String,
+    pub locator: Option<String>,
+    pub number_of_records: Option<u64>,
+}
+
+impl BulkQueryResultChunk {
+    /// Salesforce sends the literal string `null` as the locator of the final chunk.
+    pub(crate) fn parse_locator(header_value: Option<&str>) -> Option<String> {
+        match header_value {
+            None | Some("") | Some("null") => None,
+            Some(locator) => Some(locator.to_string()),
+        }
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.locator.is_none()
+    }
+
+    /// Parses the CSV contained in the chunk into JSON records.
+    pub fn records(&self) -> ServiceResult<Vec<Value>> {
+        csv_to_records(&self.csv)
+    }
+}
+
+/// Representation a client would like bulk query results returned in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkResultFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkQueryResultsParameters {
+    pub locator: Option<String>,
+    pub max_records: Option<u32>,
+    #[serde(default)]
+    pub format: BulkResultFormat,
+}
+
+/// The caller a bulk query job was created for and the object it queries.
+#[derive(Debug, Clone)]
+pub struct BulkQueryJobOwner {
+    pub caller: String,
+    pub object: String,
+    created_at: Instant,
+}
+
+/// Records who created each bulk query job, so only they can poll it or read its results with the
+/// access they had to the queried object.
+#[derive(Debug, Default)]
+pub struct BulkQueryJobOwners {
+    jobs: Mutex<HashMap<(SalesforceOrganization, String), BulkQueryJobOwner>>,
+}
+
+impl BulkQueryJobOwners {
+    pub fn record(
+        &self,
+        organization: SalesforceOrganization,
+        job_id: &str,
+        caller: &str,
+        object: &str,
+    ) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, owner| owner.created_at.elapsed() < BULK_JOB_RETENTION);
+        jobs.insert(
+            (organization, job_id.to_string()),
+            BulkQueryJobOwner {
+                caller: caller.to_string(),
+                object: object.to_string(),
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    /// The owner of a job created through the API, or `None` for jobs it didn't create.
+    pub fn owner(
+        &self,
+        organization: SalesforceOrganization,
+        job_id: &str,
+    ) -> Option<BulkQueryJobOwner> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&(organization, job_id.to_string()))
+            .filter(|owner| owner.created_at.elapsed() < BULK_JOB_RETENTION)
+            .cloned()
+    }
+}
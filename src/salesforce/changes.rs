@@ -0,0 +1,45 @@
+//! Field level differences between a record's values before and after an update.
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+/// Compares the values written against the record's values beforehand, reporting only the fields
+/// that changed. Fields missing from `before`, such as those hidden by a field policy, are skipped.
+pub fn diff_record(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let (Value::Object(before), Value::Object(after)) = (before, after) else {
+        return Vec::new();
+    };
+
+    after
+        .iter()
+        .filter(|(field, _)| *field != "attributes")
+        .filter_map(|(field, new_value)| {
+            // Salesforce returns field names in their canonical case, whatever the caller sent
+            let (_, old_value) = before
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(field))?;
+
+            (!values_equal(old_value, new_value)).then(|| FieldChange {
+                field: field.clone(),
+                old_value: old_value.clone(),
+                new_value: new_value.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Compares numbers by value, as Salesforce returns `10.0` for a currency field written as `10`.
+fn values_equal(old_value: &Value, new_value: &Value) -> bool {
+    match (old_value.as_f64(), new_value.as_f64()) {
+        (Some(old_number), Some(new_number)) => old_number == new_number,
+        _ => old_value == new_value,
+    }
+}
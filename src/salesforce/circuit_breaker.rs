@@ -0,0 +1,330 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::errors::{ServiceError, ServiceResult};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct CircuitBreakerConfiguration {
+    /// Fraction of failed calls within the window, between zero and one, that opens the circuit.
+    pub failure_rate_threshold: f64,
+    /// Number of calls that must be recorded before the failure rate is considered.
+    pub minimum_calls: usize,
+    /// Number of most recent calls the failure rate is calculated over.
+    pub window_size: usize,
+    /// How long the circuit stays open before letting probe calls through.
+    pub open_seconds: u64,
+    /// Number of successful probe calls required to close the circuit again.
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerConfiguration {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            minimum_calls: 10,
+            window_size: 20,
+            open_seconds: 30,
+            half_open_probes: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CircuitState {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, successes: u32 },
+}
+
+#[derive(Debug)]
+struct CircuitBreakerInner {
+    state: CircuitState,
+    outcomes: VecDeque<bool>,
+    /// Incremented each time the circuit half opens, so probes from an earlier attempt at closing
+    /// it can't affect the current one.
+    probe_generation: u64,
+}
+
+impl CircuitBreakerInner {
+    fn failure_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+
+        let failures = self
+            .outcomes
+            .iter()
+            .filter(|succeeded| !**succeeded)
+            .count();
+        failures as f64 / self.outcomes.len() as f64
+    }
+}
+
+/// Point in time view of a circuit breaker, reported on the health endpoints.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitSnapshot {
+    pub state: &'static str,
+    pub failure_rate: f64,
+    pub recorded_calls: usize,
+    pub retry_after_seconds: Option<u64>,
+}
+
+impl CircuitSnapshot {
+    pub fn is_open(&self) -> bool {
+        self.state == "open"
+    }
+}
+
+/// Tracks the outcome of calls to a single organization, failing fast once enough of them fail
+/// so callers aren't left waiting on an organization that's having an outage.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfiguration,
+    inner: Mutex<CircuitBreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfiguration) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(CircuitBreakerInner {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::with_capacity(config.window_size),
+                probe_generation: 0,
+            }),
+        }
+    }
+
+    /// Asks permission to make a call, returning how long the caller should wait before trying
+    /// again when the circuit is not accepting calls. The outcome is reported through the permit,
+    /// and a permit dropped without one gives its probe slot back.
+    pub fn try_acquire(&self) -> Result<CircuitPermit<'_>, Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let probe = match inner.state {
+            CircuitState::Closed => None,
+            CircuitState::Open { until } if now < until => return Err(until - now),
+            CircuitState::Open { .. } => {
+                inner.probe_generation += 1;
+                inner.state = CircuitState::HalfOpen {
+                    in_flight: 1,
+                    successes: 0,
+                };
+                Some(inner.probe_generation)
+            }
+            CircuitState::HalfOpen {
+                in_flight,
+                successes,
+            } if in_flight < self.config.half_open_probes => {
+                inner.state = CircuitState::HalfOpen {
+                    in_flight: in_flight + 1,
+                    successes,
+                };
+                Some(inner.probe_generation)
+            }
+            // Probes are already in flight, so it'll be known shortly whether the circuit closes
+            CircuitState::HalfOpen { .. } => return Err(Duration::from_secs(1)),
+        };
+
+        Ok(CircuitPermit {
+            breaker: self,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn record(&self, succeeded: bool, probe: Option<u64>) {
+        let mut inner = self.inner.lock().unwrap();
+        let current_probe = probe == Some(inner.probe_generation);
+
+        match inner.state {
+            CircuitState::Closed => {
+                inner.outcomes.push_back(succeeded);
+                while inner.outcomes.len() > self.config.window_size {
+                    inner.outcomes.pop_front();
+                }
+
+                let tripped = inner.outcomes.len() >= self.config.minimum_calls
+                    && inner.failure_rate() >= self.config.failure_rate_threshold;
+
+                if tripped {
+                    self.open(&mut inner);
+                }
+            }
+            // Calls permitted before the circuit half opened say nothing about whether it recovered
+            CircuitState::HalfOpen { .. } if !current_probe => {}
+            CircuitState::HalfOpen { .. } if !succeeded => self.open(&mut inner),
+            CircuitState::HalfOpen {
+                in_flight,
+                successes,
+            } => {
+                if successes + 1 >= self.config.half_open_probes {
+                    inner.state = CircuitState::Closed;
+                    inner.outcomes.clear();
+                } else {
+                    inner.state = CircuitState::HalfOpen {
+                        in_flight: in_flight.saturating_sub(1),
+                        successes: successes + 1,
+                    };
+                }
+            }
+            // A call that started before the circuit opened has nothing left to influence
+            CircuitState::Open { .. } => {}
+        }
+    }
+
+    /// Gives back the slot of a probe that ended without an outcome, such as when the request
+    /// was cancelled, so the circuit isn't left half open with no probes able to close it.
+    fn release(&self, probe: u64) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let CircuitState::HalfOpen {
+            in_flight,
+            successes,
+        } = inner.state
+        {
+            if probe == inner.probe_generation {
+                inner.state = CircuitState::HalfOpen {
+                    in_flight: in_flight.saturating_sub(1),
+                    successes,
+                };
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> CircuitSnapshot {
+        let inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let (state, retry_after_seconds) = match inner.state {
+            CircuitState::Closed => ("closed", None),
+            CircuitState::Open { until } if now < until => {
+                ("open", Some((until - now).as_secs().max(1)))
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => ("half_open", None),
+        };
+
+        CircuitSnapshot {
+            state,
+            failure_rate: inner.failure_rate(),
+            recorded_calls: inner.outcomes.len(),
+            retry_after_seconds,
+        }
+    }
+
+    fn open(&self, inner: &mut CircuitBreakerInner) {
+        inner.state = CircuitState::Open {
+            until: Instant::now() + Duration::from_secs(self.config.open_seconds),
+        };
+        inner.outcomes.clear();
+    }
+
+    /// Determines if a call indicates the organization itself is unhealthy. Client errors mean
+    /// Salesforce is up and answering, so only transport failures and server errors count.
+    pub fn is_failure(result: &ServiceResult<Response>) -> bool {
+        match result {
+            Ok(_) => false,
+            Err(ServiceError::ClientRequestFailed(_)) => true,
+            Err(ServiceError::SalesforceRequestFailed(status, _)) => *status >= 500,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Permission to make a single call through a [`CircuitBreaker`], reporting its outcome.
+#[derive(Debug)]
+#[must_use = "the outcome of the call should be recorded"]
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    /// The half open generation the call is probing, if it was permitted as a probe.
+    probe: Option<u64>,
+    recorded: bool,
+}
+
+impl CircuitPermit<'_> {
+    pub fn record(mut self, succeeded: bool) {
+        self.recorded = true;
+        self.breaker.record(succeeded, self.probe);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if let (false, Some(probe)) = (self.recorded, self.probe) {
+            self.breaker.release(probe);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfiguration {
+            failure_rate_threshold: 0.5,
+            minimum_calls: 2,
+            window_size: 2,
+            open_seconds: 30,
+            half_open_probes: 1,
+        })
+    }
+
+    fn trip(breaker: &CircuitBreaker) {
+        for _ in 0..2 {
+            breaker.try_acquire().unwrap().record(false);
+        }
+        assert!(breaker.snapshot().is_open());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_probes_give_back_their_slot() {
+        let breaker = breaker();
+        trip(&breaker);
+        tokio::time::advance(Duration::from_secs(31)).await;
+
+        let probe = breaker.try_acquire().unwrap();
+        assert!(breaker.try_acquire().is_err());
+
+        // A cancelled request drops its permit without recording an outcome
+        drop(probe);
+
+        breaker.try_acquire().unwrap().record(true);
+        assert_eq!(breaker.snapshot().state, "closed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn calls_permitted_before_half_opening_are_not_probes() {
+        let breaker = breaker();
+        let earlier = breaker.try_acquire().unwrap();
+        trip(&breaker);
+        tokio::time::advance(Duration::from_secs(31)).await;
+
+        let probe = breaker.try_acquire().unwrap();
+        earlier.record(false);
+        assert_eq!(breaker.snapshot().state, "half_open");
+
+        probe.record(true);
+        assert_eq!(breaker.snapshot().state, "closed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_probes_reopen_the_circuit() {
+        let breaker = breaker();
+        trip(&breaker);
+        tokio::time::advance(Duration::from_secs(31)).await;
+
+        breaker.try_acquire().unwrap().record(false);
+
+        assert!(breaker.snapshot().is_open());
+        assert!(breaker.try_acquire().is_err());
+    }
+}
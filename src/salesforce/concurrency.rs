@@ -0,0 +1,89 @@
+//! Optimistic concurrency for record writes. Reads report the version of the record they returned,
+//! which callers send back with their update so Salesforce rejects it if someone else has changed
+//! the record in between.
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, ETAG, IF_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED};
+use reqwest::RequestBuilder;
+use serde_json::Value;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+/// How Salesforce formats `LastModifiedDate`, e.g. `2024-03-01T17:04:11.000+0000`.
+const SALESFORCE_DATE_TIME: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3][offset_hour sign:mandatory][offset_minute]"
+);
+
+/// The date format used in HTTP headers, e.g. `Fri, 01 Mar 2024 17:04:11 GMT`.
+const HTTP_DATE: &[FormatItem<'_>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// A record along with the version it was read at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionedRecord {
+    pub record: Value,
+    pub version: RecordVersion,
+    /// How much longer the record may be reused for, when its object is cached.
+    pub max_age: Option<Duration>,
+}
+
+/// The version of a record as of when it was read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordVersion {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl RecordVersion {
+    /// Takes the version from Salesforce's response headers, falling back to the record's
+    /// `LastModifiedDate` when Salesforce didn't send a `Last-Modified` header.
+    pub fn from_response(headers: &HeaderMap, record: &Value) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED).or_else(|| last_modified_date(record)),
+        }
+    }
+}
+
+fn last_modified_date(record: &Value) -> Option<String> {
+    let last_modified = record.get("LastModifiedDate")?.as_str()?;
+
+    OffsetDateTime::parse(last_modified, SALESFORCE_DATE_TIME)
+        .ok()?
+        .to_offset(UtcOffset::UTC)
+        .format(HTTP_DATE)
+        .ok()
+}
+
+/// Conditions a write only proceeds under, evaluated by Salesforce against the record's current
+/// version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WritePreconditions {
+    /// Only write if the record's ETag still matches.
+    pub if_match: Option<String>,
+    /// Only write if the record hasn't been modified since, as an HTTP date.
+    pub if_unmodified_since: Option<String>,
+}
+
+impl WritePreconditions {
+    /// Adds the conditional request headers Salesforce evaluates the preconditions with.
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(if_match) = &self.if_match {
+            request = request.header(IF_MATCH, if_match);
+        }
+        if let Some(if_unmodified_since) = &self.if_unmodified_since {
+            request = request.header(IF_UNMODIFIED_SINCE, if_unmodified_since);
+        }
+        request
+    }
+}
@@ -0,0 +1,431 @@
+//! Conversions between the CSV spoken by the Bulk API and the JSON records used everywhere else.
+//!
+//! Relationship fields are flattened into dotted column names (e.g. `Owner.Name`), JSON nulls are
+//! written as `#N/A` so Salesforce clears the field rather than ignoring it, and every value is
+//! quoted. When parsing result CSVs back into records, both empty values and `#N/A` become `null`
+//! and all other values are kept as strings, as the CSV carries no type information. Values can be
+//! typed again using the field types from describe metadata with [`from_salesforce`].
+
+use std::collections::HashMap;
+
+use csv::{QuoteStyle, ReaderBuilder, WriterBuilder};
+use serde_json::{Map, Number, Value};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+use crate::errors::{ServiceError, ServiceResult};
+use crate::redaction::REDACTED;
+use crate::salesforce::describe::SObjectDescribe;
+use crate::salesforce::field_policy::ReadAccess;
+
+/// Value Salesforce interprets as an explicit null in bulk CSV payloads.
+pub const CSV_NULL_VALUE: &str = "#N/A";
+
+/// Metadata key Salesforce attaches to every record and relationship, which has no CSV column.
+const ATTRIBUTES_KEY: &str = "attributes";
+
+const RELATIONSHIP_SEPARATOR: char = '.';
+
+const DATE: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]");
+
+/// How the Bulk API writes date times, always in UTC, e.g. `2024-03-01T17:04:11.000Z`.
+const DATE_TIME: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+
+/// How the REST API writes date times, e.g. `2024-03-01T17:04:11.000+0000`.
+const OFFSET_DATE_TIME: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3][offset_hour sign:mandatory][offset_minute]"
+);
+
+/// Writes a JSON value the way Salesforce expects it in a CSV cell.
+pub fn to_salesforce(value: &Value) -> String {
+    match value {
+        Value::Null => CSV_NULL_VALUE.to_string(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Reads a CSV cell back into a JSON value of the field's describe type. Numbers become JSON
+/// numbers, booleans JSON booleans, and dates and date times are normalized to the Bulk API's
+/// formats. Other types are kept as strings.
+pub fn from_salesforce(field_type: &str, raw: &str) -> ServiceResult<Value> {
+    if raw.is_empty() || raw == CSV_NULL_VALUE {
+        return Ok(Value::Null);
+    }
+
+    let invalid =
+        || ServiceError::CsvConversionFailed(format!("{raw} is not a valid {field_type}."));
+
+    let value = match field_type {
+        "boolean" => match raw.to_ascii_lowercase().as_str() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return Err(invalid()),
+        },
+        "int" => raw.parse::<i64>().map(Value::from).map_err(|_| invalid())?,
+        "double" | "currency" | "percent" => raw
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(invalid)?,
+        "date" => {
+            let date = Date::parse(raw, DATE).map_err(|_| invalid())?;
+            Value::String(date.format(DATE).map_err(|_| invalid())?)
+        }
+        "datetime" => {
+            let date_time = PrimitiveDateTime::parse(raw, DATE_TIME)
+                .map(PrimitiveDateTime::assume_utc)
+                .or_else(|_| OffsetDateTime::parse(raw, OFFSET_DATE_TIME))
+                .map_err(|_| invalid())?;
+            let formatted = date_time
+                .to_offset(UtcOffset::UTC)
+                .format(DATE_TIME)
+                .map_err(|_| invalid())?;
+            Value::String(formatted)
+        }
+        _ => Value::String(raw.to_string()),
+    };
+
+    Ok(value)
+}
+
+/// Flattens JSON records into a Salesforce-compatible CSV document, with columns in the order
+/// they are first seen across all records.
+pub fn records_to_csv(records: &[Value]) -> ServiceResult<String> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut flattened_records = Vec::with_capacity(records.len());
+
+    for record in records {
+        let mut flattened = HashMap::new();
+
+        match record {
+            Value::Object(fields) => flatten_fields(None, fields, &mut flattened, &mut columns)?,
+            _ => {
+                return Err(ServiceError::CsvConversionFailed(
+                    "Only JSON objects can be converted into CSV records.".to_string(),
+                ))
+            }
+        }
+
+        flattened_records.push(flattened);
+    }
+
+    // A relationship that is null on one record but populated on another would otherwise produce
+    // both an `Owner` and an `Owner.Name` column, so only the most specific columns are kept
+    let leaf_columns: Vec<&String> = columns
+        .iter()
+        .filter(|column| {
+            !columns
+                .iter()
+                .any(|other| other.starts_with(&format!("{column}{RELATIONSHIP_SEPARATOR}")))
+        })
+        .collect();
+
+    let mut writer = WriterBuilder::new()
+        .quote_style(QuoteStyle::Always)
+        .from_writer(Vec::new());
+
+    writer.write_record(&leaf_columns)?;
+
+    for flattened in flattened_records.iter() {
+        let row = leaf_columns
+            .iter()
+            .map(|column| resolve_column_value(column, flattened));
+        writer.write_record(row)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| ServiceError::CsvConversionFailed(e.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| ServiceError::CsvConversionFailed(e.to_string()))
+}
+
+/// Parses a bulk result CSV document into JSON records, nesting dotted columns into
+/// relationship objects.
+pub fn csv_to_records(csv: &str) -> ServiceResult<Vec<Value>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(csv.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let mut records = Vec::new();
+
+    for row in reader.records() {
+        let row = row?;
+        let mut record = Map::new();
+
+        for (column, raw_value) in headers.iter().zip(row.iter()) {
+            let value = match raw_value {
+                "" | CSV_NULL_VALUE => Value::Null,
+                value => Value::String(value.to_string()),
+            };
+
+            insert_nested(&mut record, column, value);
+        }
+
+        records.push(Value::Object(record));
+    }
+
+    Ok(records)
+}
+
+/// Parses a bulk result CSV document like [`csv_to_records`], typing the values of the object's own
+/// fields using its describe metadata. Relationship columns are kept as strings.
+pub fn csv_to_typed_records(csv: &str, describe: &SObjectDescribe) -> ServiceResult<Vec<Value>> {
+    let mut records = csv_to_records(csv)?;
+
+    for record in records.iter_mut().filter_map(Value::as_object_mut) {
+        for (name, value) in record.iter_mut() {
+            if let (Some(field), Value::String(raw)) = (describe.field(name), &value) {
+                *value = from_salesforce(&field.field_type, raw)?;
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// The column names of a bulk result CSV document.
+pub fn csv_columns(csv: &str) -> ServiceResult<Vec<String>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(csv.as_bytes());
+
+    Ok(reader.headers()?.iter().map(str::to_string).collect())
+}
+
+/// Rewrites a bulk result CSV document without the columns that are hidden and with the values of
+/// masked columns replaced. Empty values and nulls are left as they are, as with JSON records.
+pub fn restrict_csv_columns(
+    csv: &str,
+    access: impl Fn(&str) -> ReadAccess,
+) -> ServiceResult<String> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(csv.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let columns: Vec<(usize, ReadAccess)> = headers
+        .iter()
+        .map(&access)
+        .enumerate()
+        .filter(|(_, access)| *access != ReadAccess::Hidden)
+        .collect();
+
+    let mut writer = WriterBuilder::new()
+        .quote_style(QuoteStyle::Always)
+        .from_writer(Vec::new());
+
+    writer.write_record(columns.iter().map(|(index, _)| &headers[*index]))?;
+
+    for row in reader.records() {
+        let row = row?;
+        writer.write_record(columns.iter().map(|(index, access)| match &row[*index] {
+            value @ ("" | CSV_NULL_VALUE) => value,
+            _ if *access == ReadAccess::Masked => REDACTED,
+            value => value,
+        }))?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| ServiceError::CsvConversionFailed(e.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| ServiceError::CsvConversionFailed(e.to_string()))
+}
+
+fn flatten_fields(
+    prefix: Option<&str>,
+    fields: &Map<String, Value>,
+    flattened: &mut HashMap<String, Option<String>>,
+    columns: &mut Vec<String>,
+) -> ServiceResult<()> {
+    for (name, value) in fields {
+        if name == ATTRIBUTES_KEY {
+            continue;
+        }
+
+        let column = match prefix {
+            None => name.clone(),
+            Some(prefix) => format!("{prefix}{RELATIONSHIP_SEPARATOR}{name}"),
+        };
+
+        let flattened_value = match value {
+            Value::Null => None,
+            Value::Bool(_) | Value::Number(_) | Value::String(_) => Some(to_salesforce(value)),
+            Value::Object(relationship) => {
+                flatten_fields(Some(&column), relationship, flattened, columns)?;
+                continue;
+            }
+            Value::Array(_) => {
+                return Err(ServiceError::CsvConversionFailed(format!(
+                    "Field {column} contains a list, which cannot be represented in CSV."
+                )))
+            }
+        };
+
+        if !columns.contains(&column) {
+            columns.push(column.clone());
+        }
+
+        flattened.insert(column, flattened_value);
+    }
+
+    Ok(())
+}
+
+fn resolve_column_value(column: &str, flattened: &HashMap<String, Option<String>>) -> String {
+    if let Some(value) = flattened.get(column) {
+        return value.clone().unwrap_or_else(|| CSV_NULL_VALUE.to_string());
+    }
+
+    // Fields beneath a null relationship are themselves null
+    let mut ancestor = column;
+    while let Some((parent, _)) = ancestor.rsplit_once(RELATIONSHIP_SEPARATOR) {
+        if let Some(None) = flattened.get(parent) {
+            return CSV_NULL_VALUE.to_string();
+        }
+        ancestor = parent;
+    }
+
+    String::new()
+}
+
+fn insert_nested(record: &mut Map<String, Value>, column: &str, value: Value) {
+    match column.split_once(RELATIONSHIP_SEPARATOR) {
+        None => {
+            record.insert(column.to_string(), value);
+        }
+        Some((relationship, rest)) => {
+            let entry = record
+                .entry(relationship.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+
+            if let Value::Object(nested) = entry {
+                insert_nested(nested, rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde_json::json;
+    use time::{Duration, OffsetDateTime};
+
+    use super::*;
+
+    fn round_trip(field_type: &str, value: &Value) -> Value {
+        from_salesforce(field_type, &to_salesforce(value)).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn booleans_round_trip(value: bool) {
+            let value = Value::Bool(value);
+            prop_assert_eq!(round_trip("boolean", &value), value);
+        }
+
+        #[test]
+        fn integers_round_trip(value: i64) {
+            let value = Value::from(value);
+            prop_assert_eq!(round_trip("int", &value), value);
+        }
+
+        #[test]
+        fn decimals_round_trip(
+            value in any::<f64>().prop_filter("finite", |value| value.is_finite()),
+            field_type in prop_oneof!["double", "currency", "percent"],
+        ) {
+            let value = Value::from(value);
+            prop_assert_eq!(round_trip(&field_type, &value), value);
+        }
+
+        #[test]
+        fn dates_round_trip(days in 0i64..3_000_000) {
+            let date = Date::MIN.saturating_add(Duration::days(days));
+            let value = Value::String(date.format(DATE).unwrap());
+            prop_assert_eq!(round_trip("date", &value), value);
+        }
+
+        #[test]
+        fn date_times_round_trip(millis in 0i64..253_402_300_799_000) {
+            let date_time = OffsetDateTime::UNIX_EPOCH + Duration::milliseconds(millis);
+            let value = Value::String(date_time.format(DATE_TIME).unwrap());
+            prop_assert_eq!(round_trip("datetime", &value), value);
+        }
+
+        #[test]
+        fn nulls_round_trip(
+            field_type in prop_oneof!["boolean", "int", "double", "date", "datetime", "string"],
+        ) {
+            prop_assert_eq!(round_trip(&field_type, &Value::Null), Value::Null);
+        }
+
+        #[test]
+        fn strings_round_trip(value in "[^\u{0}]+".prop_filter("not null", |value| value != CSV_NULL_VALUE)) {
+            let value = Value::String(value);
+            prop_assert_eq!(round_trip("string", &value), value);
+        }
+
+        #[test]
+        fn records_round_trip(
+            names in prop::collection::vec(("[A-Z][a-z]{0,8}", "[A-Za-z0-9 ,\"\n]{1,12}"), 1..4),
+            owner in prop::option::of("[A-Za-z ,\"]{1,12}"),
+        ) {
+            let mut record = Map::new();
+            for (name, value) in &names {
+                record.insert(name.clone(), Value::String(value.clone()));
+            }
+            record.insert(
+                "Owner".to_string(),
+                owner.map_or(json!({ "Name": null }), |name| json!({ "Name": name })),
+            );
+            let record = Value::Object(record);
+
+            let csv = records_to_csv(std::slice::from_ref(&record)).unwrap();
+            prop_assert_eq!(csv_to_records(&csv).unwrap(), vec![record]);
+        }
+    }
+
+    #[test]
+    fn rest_date_times_are_normalized() {
+        assert_eq!(
+            from_salesforce("datetime", "2024-03-01T12:04:11.000-0500").unwrap(),
+            json!("2024-03-01T17:04:11.000Z")
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(from_salesforce("boolean", "yes").is_err());
+        assert!(from_salesforce("int", "1.5").is_err());
+        assert!(from_salesforce("date", "2024-02-30").is_err());
+    }
+
+    #[test]
+    fn restricted_columns_are_removed_or_masked() {
+        let csv = "\"Id\",\"SSN__c\",\"Phone\"\n\"001\",\"123-45-6789\",\"555-0100\"\n\"002\",\"\",\"\"\n";
+
+        let restricted = restrict_csv_columns(csv, |column| match column {
+            "SSN__c" => ReadAccess::Hidden,
+            "Phone" => ReadAccess::Masked,
+            _ => ReadAccess::Visible,
+        })
+        .unwrap();
+
+        assert_eq!(
+            restricted,
+            "\"Id\",\"Phone\"\n\"001\",\"***\"\n\"002\",\"\"\n"
+        );
+    }
+}
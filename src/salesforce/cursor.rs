@@ -0,0 +1,144 @@
+//! Cursors for paging through the child records of a relationship. Salesforce's query locators
+//! aren't tied to the query that produced them, so a locator from one query could otherwise be
+//! used to page through the results of another. Cursors carry the locator along with a checksum
+//! over it and the parent record and relationship it was issued for, keyed with a secret derived
+//! from the organization's credentials so every instance of the service accepts them.
+
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::{ServiceError, ServiceResult};
+
+/// Separates the locator from its checksum, which locators never contain.
+const CHECKSUM_SEPARATOR: char = '.';
+
+#[derive(Clone, Copy)]
+pub struct RelatedRecordsCursors {
+    secret: [u8; 32],
+}
+
+// The secret is left out so it can't leak through spans or error output
+impl fmt::Debug for RelatedRecordsCursors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelatedRecordsCursors")
+            .finish_non_exhaustive()
+    }
+}
+
+impl RelatedRecordsCursors {
+    pub fn new(consumer_secret: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"related-records-cursor\0");
+        hasher.update(consumer_secret.as_bytes());
+
+        Self {
+            secret: hasher.finalize().into(),
+        }
+    }
+
+    /// Issues the cursor for the next page of a relationship's records.
+    pub fn encode(&self, object: &str, id: &str, relationship: &str, locator: &str) -> String {
+        format!(
+            "{locator}{CHECKSUM_SEPARATOR}{}",
+            self.checksum(object, id, relationship, locator)
+        )
+    }
+
+    /// The query locator in a cursor, provided it was issued for the same record and relationship.
+    pub fn decode(
+        &self,
+        object: &str,
+        id: &str,
+        relationship: &str,
+        cursor: &str,
+    ) -> ServiceResult<String> {
+        let (locator, checksum) = cursor
+            .rsplit_once(CHECKSUM_SEPARATOR)
+            .filter(|(locator, _)| is_query_locator(locator))
+            .ok_or(ServiceError::CursorInvalid)?;
+
+        let expected = self.checksum(object, id, relationship, locator);
+        if !constant_time_eq(checksum.as_bytes(), expected.as_bytes()) {
+            return Err(ServiceError::CursorInvalid);
+        }
+
+        Ok(locator.to_string())
+    }
+
+    fn checksum(&self, object: &str, id: &str, relationship: &str, locator: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.secret);
+        // Names are case insensitive and ids have a 15 and 18 character form, so the checksum
+        // shouldn't depend on how the caller wrote them
+        for part in [
+            object.to_lowercase().as_str(),
+            id.get(..15).unwrap_or(id),
+            relationship.to_lowercase().as_str(),
+            locator,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update(b"\0");
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Query locators identify the next page of results, e.g. `01gD0000002HU6KIAW-2000`.
+fn is_query_locator(cursor: &str) -> bool {
+    !cursor.is_empty()
+        && cursor
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCATOR: &str = "01gD0000002HU6KIAW-2000";
+
+    #[test]
+    fn cursors_only_continue_the_relationship_they_were_issued_for() {
+        let cursors = RelatedRecordsCursors::new("secret");
+        let cursor = cursors.encode("Account", "001000000000001AAA", "Contacts", LOCATOR);
+
+        assert_eq!(
+            cursors
+                .decode("account", "001000000000001", "contacts", &cursor)
+                .unwrap(),
+            LOCATOR
+        );
+
+        for (object, id, relationship) in [
+            ("Account", "001000000000002AAA", "Contacts"),
+            ("Account", "001000000000001AAA", "Opportunities"),
+            ("Loan__c", "001000000000001AAA", "Contacts"),
+        ] {
+            assert!(cursors.decode(object, id, relationship, &cursor).is_err());
+        }
+
+        let other_organization = RelatedRecordsCursors::new("other secret");
+        assert!(other_organization
+            .decode("Account", "001000000000001AAA", "Contacts", &cursor)
+            .is_err());
+    }
+
+    #[test]
+    fn bare_or_tampered_locators_are_rejected() {
+        let cursors = RelatedRecordsCursors::new("secret");
+        let cursor = cursors.encode("Account", "001000000000001AAA", "Contacts", LOCATOR);
+        let tampered = cursor.replacen("-2000", "-4000", 1);
+
+        for cursor in [LOCATOR, tampered.as_str(), "../limits.abc", ""] {
+            assert!(cursors
+                .decode("Account", "001000000000001AAA", "Contacts", cursor)
+                .is_err());
+        }
+    }
+}
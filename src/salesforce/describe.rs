@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+/// Metadata for a single sObject as returned by the `sobjects/:name/describe` resource.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SObjectDescribe {
+    pub name: String,
+    pub label: String,
+    pub key_prefix: Option<String>,
+    pub custom: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub deletable: bool,
+    pub queryable: bool,
+    pub fields: Vec<FieldDescribe>,
+    #[serde(default)]
+    pub child_relationships: Vec<ChildRelationship>,
+}
+
+impl SObjectDescribe {
+    /// Finds a field by API name, which Salesforce treats case-insensitively.
+    pub fn field(&self, name: &str) -> Option<&FieldDescribe> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Finds the lookup field behind a relationship name, such as `AccountId` for `Account`.
+    pub fn lookup_field(&self, relationship_name: &str) -> Option<&FieldDescribe> {
+        self.fields.iter().find(|field| {
+            !field.reference_to.is_empty()
+                && field
+                    .relationship_name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(relationship_name))
+        })
+    }
+
+    /// Finds a relationship by name, either a lookup to a parent record or a list of child records.
+    pub fn relationship(&self, name: &str) -> Option<Relationship> {
+        let is_named = |relationship_name: &Option<String>| {
+            relationship_name
+                .as_deref()
+                .is_some_and(|relationship_name| relationship_name.eq_ignore_ascii_case(name))
+        };
+
+        let lookup = self.lookup_field(name).map(|field| Relationship::Lookup {
+            name: field.relationship_name.clone().unwrap_or_default(),
+            field: field.name.clone(),
+            objects: field.reference_to.clone(),
+        });
+
+        lookup.or_else(|| {
+            self.child_relationships
+                .iter()
+                .find(|child| is_named(&child.relationship_name))
+                .map(|child| Relationship::Child {
+                    name: child.relationship_name.clone().unwrap_or_default(),
+                    object: child.child_s_object.clone(),
+                })
+        })
+    }
+}
+
+/// A relationship from an object's records to other records, named as in SOQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Relationship {
+    /// A lookup or master-detail field, referencing a single record of one of the objects.
+    Lookup {
+        name: String,
+        field: String,
+        objects: Vec<String>,
+    },
+    /// The records of the child object that look up to the record.
+    Child { name: String, object: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDescribe {
+    pub name: String,
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub length: u32,
+    pub precision: u32,
+    pub scale: u32,
+    pub nillable: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub defaulted_on_create: bool,
+    pub calculated: bool,
+    pub custom: bool,
+    pub unique: bool,
+    pub external_id: bool,
+    #[serde(default)]
+    pub restricted_picklist: bool,
+    #[serde(default)]
+    pub picklist_values: Vec<PicklistValue>,
+    #[serde(default)]
+    pub reference_to: Vec<String>,
+    pub relationship_name: Option<String>,
+}
+
+impl FieldDescribe {
+    /// Salesforce has no explicit required flag, a field must be provided on create when it cannot
+    /// be null and has no default value.
+    pub fn is_required(&self) -> bool {
+        self.createable && !self.nillable && !self.defaulted_on_create
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PicklistValue {
+    pub value: String,
+    pub label: Option<String>,
+    pub active: bool,
+    pub default_value: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildRelationship {
+    pub child_s_object: String,
+    pub field: String,
+    pub relationship_name: Option<String>,
+    pub cascade_delete: bool,
+}
+
+/// The list of sObjects available to the authenticated user, as returned by the `sobjects` resource.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalDescribe {
+    pub encoding: String,
+    pub max_batch_size: u32,
+    pub sobjects: Vec<SObjectSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SObjectSummary {
+    pub name: String,
+    pub label: String,
+    pub key_prefix: Option<String>,
+    pub custom: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub deletable: bool,
+    pub queryable: bool,
+}
+
+/// A describe result along with what's needed to revalidate it with `If-Modified-Since`.
+#[derive(Debug)]
+pub(crate) struct CachedDescribe<T> {
+    pub value: Arc<T>,
+    pub last_modified: Option<String>,
+    pub validated_at: Instant,
+}
+
+impl<T> CachedDescribe<T> {
+    pub fn new(value: T, last_modified: Option<String>) -> Self {
+        Self {
+            value: Arc::new(value),
+            last_modified,
+            validated_at: Instant::now(),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Field level restrictions applied to every caller's reads and writes, keeping fields like
+//! `SSN__c` out of responses and system fields out of update payloads.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::redaction::REDACTED;
+
+/// Policies configured under this name apply to every object.
+pub const ALL_OBJECTS: &str = "*";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ObjectFieldPolicy {
+    /// Fields that may be read, or every field not hidden when omitted.
+    pub readable: Option<Vec<String>>,
+    /// Fields removed from records before they're returned.
+    pub hidden: Vec<String>,
+    /// Fields returned with their values masked.
+    pub masked: Vec<String>,
+    /// Fields that may be written, or every field not read only when omitted.
+    pub writable: Option<Vec<String>>,
+    /// Fields that may never be written.
+    pub read_only: Vec<String>,
+}
+
+/// Field policies for an organization keyed by object name, where `*` applies to all objects.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct FieldPolicyConfiguration(pub HashMap<String, ObjectFieldPolicy>);
+
+/// How a field may be read, ordered from least to most restrictive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReadAccess {
+    Visible,
+    Masked,
+    Hidden,
+}
+
+/// An object's field policy with names lowercased, as Salesforce field names are case insensitive.
+#[derive(Debug, Default)]
+struct FieldRules {
+    readable: Option<HashSet<String>>,
+    hidden: HashSet<String>,
+    masked: HashSet<String>,
+    writable: Option<HashSet<String>>,
+    read_only: HashSet<String>,
+}
+
+impl From<&ObjectFieldPolicy> for FieldRules {
+    fn from(policy: &ObjectFieldPolicy) -> Self {
+        fn lowercase(fields: &[String]) -> HashSet<String> {
+            fields.iter().map(|field| field.to_lowercase()).collect()
+        }
+
+        Self {
+            readable: policy.readable.as_deref().map(lowercase),
+            hidden: lowercase(&policy.hidden),
+            masked: lowercase(&policy.masked),
+            writable: policy.writable.as_deref().map(lowercase),
+            read_only: lowercase(&policy.read_only),
+        }
+    }
+}
+
+impl FieldRules {
+    fn read_access(&self, field: &str) -> ReadAccess {
+        let hidden = self.hidden.contains(field)
+            || self
+                .readable
+                .as_ref()
+                .is_some_and(|readable| !readable.contains(field));
+
+        if hidden {
+            ReadAccess::Hidden
+        } else if self.masked.contains(field) {
+            ReadAccess::Masked
+        } else {
+            ReadAccess::Visible
+        }
+    }
+
+    fn allows_write(&self, field: &str) -> bool {
+        !self.read_only.contains(field)
+            && self
+                .writable
+                .as_ref()
+                .is_none_or(|writable| writable.contains(field))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FieldPolicies {
+    objects: HashMap<String, FieldRules>,
+}
+
+impl FieldPolicies {
+    pub fn new(config: &FieldPolicyConfiguration) -> Self {
+        Self {
+            objects: config
+                .0
+                .iter()
+                .map(|(object, policy)| (object.to_lowercase(), FieldRules::from(policy)))
+                .collect(),
+        }
+    }
+
+    fn rules<'a>(&'a self, object: &str) -> impl Iterator<Item = &'a FieldRules> {
+        [ALL_OBJECTS.to_string(), object.to_lowercase()]
+            .into_iter()
+            .filter_map(|object| self.objects.get(&object))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The most restrictive access any policy for the object grants to the field.
+    pub fn read_access(&self, object: &str, field: &str) -> ReadAccess {
+        let field = field.to_lowercase();
+
+        self.rules(object)
+            .map(|rules| rules.read_access(&field))
+            .max()
+            .unwrap_or(ReadAccess::Visible)
+    }
+
+    /// Strips hidden fields and masks masked ones in a record or query result. Nested relationship
+    /// records are handled according to the object type in their `attributes`, and `object` is
+    /// used for the top level record when it has none.
+    pub fn apply_to_record(&self, object: Option<&str>, value: &mut Value) {
+        if self.objects.is_empty() {
+            return;
+        }
+
+        match value {
+            Value::Object(fields) => {
+                if let Some(object) = record_type(fields).or(object.map(str::to_string)) {
+                    fields.retain(|name, _| {
+                        name == "attributes"
+                            || self.read_access(&object, name) != ReadAccess::Hidden
+                    });
+
+                    for (name, value) in fields.iter_mut() {
+                        if name != "attributes"
+                            && !value.is_null()
+                            && self.read_access(&object, name) == ReadAccess::Masked
+                        {
+                            *value = Value::String(REDACTED.to_string());
+                        }
+                    }
+                }
+
+                fields
+                    .iter_mut()
+                    .filter(|(name, _)| *name != "attributes")
+                    .for_each(|(_, value)| self.apply_to_record(None, value));
+            }
+            Value::Array(values) => values
+                .iter_mut()
+                .for_each(|value| self.apply_to_record(None, value)),
+            _ => {}
+        }
+    }
+
+    /// The fields in an update databag that may not be written.
+    pub fn forbidden_writes(&self, object: &str, databag: &Value) -> Vec<String> {
+        let Value::Object(fields) = databag else {
+            return Vec::new();
+        };
+
+        fields
+            .keys()
+            .filter(|name| *name != "attributes")
+            .filter(|name| self.forbids_write(object, name))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether any policy for the object forbids writing the field.
+    pub fn forbids_write(&self, object: &str, field: &str) -> bool {
+        let field = field.to_lowercase();
+        self.rules(object).any(|rules| !rules.allows_write(&field))
+    }
+}
+
+/// The object type Salesforce describes a record with.
+fn record_type(fields: &Map<String, Value>) -> Option<String> {
+    fields
+        .get("attributes")
+        .and_then(|attributes| attributes.get("type"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
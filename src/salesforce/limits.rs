@@ -0,0 +1,210 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::salesforce::operation::SalesforceOperation;
+
+/// Header Salesforce attaches to every REST response reporting the organization's API usage.
+pub const SFORCE_LIMIT_INFO_HEADER: &str = "Sforce-Limit-Info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ApiLimitAction {
+    /// Non-critical requests are rejected once usage crosses the threshold.
+    Reject,
+    /// Non-critical requests are delayed once usage crosses the threshold, spreading out the
+    /// remaining allotment.
+    Throttle,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ApiLimitConfiguration {
+    /// Percentage of the daily API allotment, between zero and one hundred, above which
+    /// non-critical requests are restricted.
+    pub threshold_percent: f64,
+    pub action: ApiLimitAction,
+    pub throttle_delay_millis: u64,
+    /// How long usage above the threshold is trusted before a rejected request is let through to
+    /// report it again, as usage only falls once older requests leave the rolling 24 hour window.
+    pub refresh_after_seconds: u64,
+}
+
+impl Default for ApiLimitConfiguration {
+    fn default() -> Self {
+        Self {
+            threshold_percent: 90.0,
+            action: ApiLimitAction::Reject,
+            throttle_delay_millis: 1000,
+            refresh_after_seconds: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiUsage {
+    pub used: u64,
+    pub max: u64,
+    pub percent_used: f64,
+}
+
+impl ApiUsage {
+    fn new(used: u64, max: u64) -> Self {
+        let percent_used = if max == 0 {
+            0.0
+        } else {
+            used as f64 / max as f64 * 100.0
+        };
+
+        Self {
+            used,
+            max,
+            percent_used,
+        }
+    }
+
+    /// Parses the `api-usage=N/M` entry of an `Sforce-Limit-Info` header, ignoring any per-app
+    /// usage entries that accompany it.
+    pub fn parse(header_value: &str) -> Option<Self> {
+        header_value
+            .split(',')
+            .map(str::trim)
+            .find_map(|entry| entry.strip_prefix("api-usage="))
+            .and_then(|usage| usage.split_once('/'))
+            .and_then(|(used, max)| {
+                let used = used.trim().parse::<u64>().ok()?;
+                let max = max.trim().parse::<u64>().ok()?;
+                Some(Self::new(used, max))
+            })
+    }
+
+    /// Reads usage from the `DailyApiRequests` entry of the `/limits` REST resource.
+    pub fn from_limits_resource(limits: &Value) -> Option<Self> {
+        let daily = limits.get("DailyApiRequests")?;
+        let max = daily.get("Max")?.as_u64()?;
+        let remaining = daily.get("Remaining")?.as_u64()?;
+
+        Some(Self::new(max.saturating_sub(remaining), max))
+    }
+}
+
+/// What should happen to a request given the organization's current API usage.
+#[derive(Debug, Clone, Copy)]
+pub enum ApiLimitDecision {
+    Proceed,
+    Delay(Duration),
+    Reject(ApiUsage),
+}
+
+/// Keeps the most recently reported API usage for an organization.
+#[derive(Debug)]
+pub struct ApiUsageTracker {
+    config: ApiLimitConfiguration,
+    used: AtomicU64,
+    max: AtomicU64,
+    /// When usage was last reported, or a request was last let through to report it.
+    observed_at: Mutex<Option<Instant>>,
+}
+
+impl ApiUsageTracker {
+    pub fn new(config: ApiLimitConfiguration) -> Self {
+        Self {
+            config,
+            used: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+            observed_at: Mutex::new(None),
+        }
+    }
+
+    pub fn record(&self, usage: ApiUsage) {
+        self.used.store(usage.used, Ordering::Relaxed);
+        self.max.store(usage.max, Ordering::Relaxed);
+        *self.observed_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// The last reported usage, or `None` if Salesforce has yet to report any.
+    pub fn usage(&self) -> Option<ApiUsage> {
+        let max = self.max.load(Ordering::Relaxed);
+
+        if max == 0 {
+            return None;
+        }
+
+        Some(ApiUsage::new(self.used.load(Ordering::Relaxed), max))
+    }
+
+    pub fn decide(&self, operation: SalesforceOperation) -> ApiLimitDecision {
+        if operation.is_critical() {
+            return ApiLimitDecision::Proceed;
+        }
+
+        match self.usage() {
+            Some(usage) if usage.percent_used >= self.config.threshold_percent => {
+                match self.config.action {
+                    // Rejected requests never report usage, so one is sent once it's gone stale
+                    ApiLimitAction::Reject if self.claim_refresh() => ApiLimitDecision::Proceed,
+                    ApiLimitAction::Reject => ApiLimitDecision::Reject(usage),
+                    ApiLimitAction::Throttle => ApiLimitDecision::Delay(Duration::from_millis(
+                        self.config.throttle_delay_millis,
+                    )),
+                }
+            }
+            _ => ApiLimitDecision::Proceed,
+        }
+    }
+
+    /// Whether usage is old enough to be reported again, letting only the first request to ask
+    /// through until it has been.
+    fn claim_refresh(&self) -> bool {
+        let mut observed_at = self.observed_at.lock().unwrap();
+        let stale = observed_at.is_none_or(|observed_at| {
+            observed_at.elapsed() >= Duration::from_secs(self.config.refresh_after_seconds)
+        });
+
+        if stale {
+            *observed_at = Some(Instant::now());
+        }
+
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn rejected_requests_recover_once_usage_is_reported_again() {
+        let tracker = ApiUsageTracker::new(ApiLimitConfiguration {
+            refresh_after_seconds: 60,
+            ..ApiLimitConfiguration::default()
+        });
+        tracker.record(ApiUsage::new(95, 100));
+
+        assert!(matches!(
+            tracker.decide(SalesforceOperation::Query),
+            ApiLimitDecision::Reject(_)
+        ));
+
+        // Once usage is stale a single request is let through to report it
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(matches!(
+            tracker.decide(SalesforceOperation::Query),
+            ApiLimitDecision::Proceed
+        ));
+        assert!(matches!(
+            tracker.decide(SalesforceOperation::Query),
+            ApiLimitDecision::Reject(_)
+        ));
+
+        tracker.record(ApiUsage::new(50, 100));
+        assert!(matches!(
+            tracker.decide(SalesforceOperation::Query),
+            ApiLimitDecision::Proceed
+        ));
+    }
+}
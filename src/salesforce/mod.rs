@@ -1,2 +1,16 @@
+pub mod bulk;
+pub mod changes;
+pub mod circuit_breaker;
+pub mod concurrency;
+pub mod conversion;
+pub mod cursor;
+pub mod describe;
+pub mod field_policy;
+pub mod limits;
+pub mod operation;
+pub mod preflight;
+pub mod rate_limit;
+pub mod record_cache;
 pub mod resolver;
+pub mod retry;
 pub mod service;
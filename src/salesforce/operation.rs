@@ -0,0 +1,46 @@
+/// The kinds of calls made to Salesforce, used to decide how each call may be handled when it fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SalesforceOperation {
+    Authenticate,
+    GetRecord,
+    GetRelatedRecords,
+    Query,
+    UpdateRecord,
+    DescribeObject,
+    DescribeGlobal,
+    CreateBulkQueryJob,
+    GetBulkQueryJob,
+    GetBulkQueryResults,
+    Limits,
+}
+
+impl SalesforceOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Authenticate => "authenticate",
+            Self::GetRecord => "get_record",
+            Self::GetRelatedRecords => "get_related_records",
+            Self::Query => "query",
+            Self::UpdateRecord => "update_record",
+            Self::DescribeObject => "describe_object",
+            Self::DescribeGlobal => "describe_global",
+            Self::CreateBulkQueryJob => "create_bulk_query_job",
+            Self::GetBulkQueryJob => "get_bulk_query_job",
+            Self::GetBulkQueryResults => "get_bulk_query_results",
+            Self::Limits => "limits",
+        }
+    }
+
+    /// Determines if the operation must go through even when the organization is close to its API
+    /// limits. Writes are critical to callers, while authenticating and checking limits are needed
+    /// to keep observing usage at all.
+    pub fn is_critical(&self) -> bool {
+        matches!(self, Self::Authenticate | Self::UpdateRecord | Self::Limits)
+    }
+
+    /// Determines if repeating the operation is guaranteed to have no effects beyond the first call.
+    /// Updates may fire triggers and workflows again, and each bulk job submission creates a new job.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Self::UpdateRecord | Self::CreateBulkQueryJob)
+    }
+}
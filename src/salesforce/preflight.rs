@@ -0,0 +1,530 @@
+//! Validation of record payloads against describe metadata, performed before they're sent to
+//! Salesforce so callers learn about every problem at once rather than one 400 at a time.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::salesforce::describe::{FieldDescribe, SObjectDescribe};
+
+/// The kind of write a payload is being validated for, as creates and updates have different
+/// field level permissions and requirements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOperation {
+    Create,
+    Update,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldViolation {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldViolation {
+    fn new(field: &str, code: &'static str, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            code,
+            message,
+        }
+    }
+}
+
+/// Validates every field in the databag, collecting all violations rather than stopping at the first.
+pub fn validate_databag(
+    describe: &SObjectDescribe,
+    databag: &Value,
+    operation: WriteOperation,
+) -> Result<(), Vec<FieldViolation>> {
+    let fields = match databag {
+        Value::Object(fields) => fields,
+        _ => {
+            return Err(vec![FieldViolation::new(
+                "",
+                "INVALID_PAYLOAD",
+                "Record payloads must be JSON objects.".to_string(),
+            )])
+        }
+    };
+
+    let mut violations = Vec::new();
+
+    for (name, value) in fields.iter().filter(|(name, _)| *name != "attributes") {
+        match (describe.field(name), describe.lookup_field(name)) {
+            (Some(field), _) => validate_field(field, value, operation, &mut violations),
+            (None, Some(lookup)) => {
+                validate_relationship_reference(name, lookup, value, operation, &mut violations)
+            }
+            (None, None) => violations.push(FieldViolation::new(
+                name,
+                "UNKNOWN_FIELD",
+                format!("{name} is not a field on {}.", describe.name),
+            )),
+        }
+    }
+
+    if operation == WriteOperation::Create {
+        validate_required_fields(describe, fields, &mut violations);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Validates that every field selected for a read exists on the object.
+pub fn validate_field_selection(
+    describe: &SObjectDescribe,
+    fields: &[String],
+) -> Result<(), Vec<FieldViolation>> {
+    let violations: Vec<FieldViolation> = fields
+        .iter()
+        .filter(|name| describe.field(name).is_none())
+        .map(|name| {
+            FieldViolation::new(
+                name,
+                "UNKNOWN_FIELD",
+                format!("{name} is not a field on {}.", describe.name),
+            )
+        })
+        .collect();
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+fn validate_required_fields(
+    describe: &SObjectDescribe,
+    fields: &Map<String, Value>,
+    violations: &mut Vec<FieldViolation>,
+) {
+    for field in describe.fields.iter().filter(|field| field.is_required()) {
+        // A lookup is also set by referencing the related record through its relationship name
+        let provided = fields.keys().any(|name| {
+            name.eq_ignore_ascii_case(&field.name)
+                || field
+                    .relationship_name
+                    .as_deref()
+                    .is_some_and(|relationship| name.eq_ignore_ascii_case(relationship))
+        });
+
+        if !provided {
+            violations.push(FieldViolation::new(
+                &field.name,
+                "REQUIRED_FIELD_MISSING",
+                format!("{} is required.", field.name),
+            ));
+        }
+    }
+}
+
+/// Validates a lookup set through its relationship name, as in `"Account__r": {"Ext__c": "A-1"}`.
+/// The external id field belongs to the related object, so only the shape of the reference and the
+/// permissions of the lookup field are checked locally.
+fn validate_relationship_reference(
+    name: &str,
+    lookup: &FieldDescribe,
+    value: &Value,
+    operation: WriteOperation,
+    violations: &mut Vec<FieldViolation>,
+) {
+    if !is_writable(lookup, operation, violations) {
+        return;
+    }
+
+    let identifies_record = value.as_object().is_some_and(|reference| {
+        reference
+            .iter()
+            .filter(|(field, _)| *field != "attributes")
+            .count()
+            == 1
+    });
+
+    if !identifies_record {
+        violations.push(FieldViolation::new(
+            name,
+            "TYPE_MISMATCH",
+            format!(
+                "{name} expects an object identifying the related record by one external id field."
+            ),
+        ));
+    }
+}
+
+/// Whether the field may be written by the operation, recording a violation when it may not.
+fn is_writable(
+    field: &FieldDescribe,
+    operation: WriteOperation,
+    violations: &mut Vec<FieldViolation>,
+) -> bool {
+    let name = field.name.as_str();
+
+    match operation {
+        WriteOperation::Update if !field.updateable => {
+            violations.push(FieldViolation::new(
+                name,
+                "NOT_UPDATEABLE",
+                format!("{name} cannot be updated."),
+            ));
+            false
+        }
+        WriteOperation::Create if !field.createable => {
+            violations.push(FieldViolation::new(
+                name,
+                "NOT_CREATEABLE",
+                format!("{name} cannot be set on create."),
+            ));
+            false
+        }
+        _ => true,
+    }
+}
+
+fn validate_field(
+    field: &FieldDescribe,
+    value: &Value,
+    operation: WriteOperation,
+    violations: &mut Vec<FieldViolation>,
+) {
+    let name = field.name.as_str();
+
+    if !is_writable(field, operation, violations) {
+        return;
+    }
+
+    if value.is_null() {
+        if !field.nillable {
+            violations.push(FieldViolation::new(
+                name,
+                "NULL_NOT_ALLOWED",
+                format!("{name} cannot be null."),
+            ));
+        }
+        return;
+    }
+
+    // Salesforce coerces strings holding numbers and booleans, and scalars sent for text fields, so
+    // only values it would refuse are rejected here
+    let type_matches = match field.field_type.as_str() {
+        "boolean" => {
+            value.is_boolean()
+                || value.as_str().is_some_and(|text| {
+                    text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false")
+                })
+        }
+        "int" => {
+            value.is_i64()
+                || value.is_u64()
+                || value
+                    .as_str()
+                    .is_some_and(|text| text.trim().parse::<i64>().is_ok())
+        }
+        "double" | "currency" | "percent" => {
+            value.is_number()
+                || value
+                    .as_str()
+                    .and_then(|text| text.trim().parse::<f64>().ok())
+                    .is_some_and(f64::is_finite)
+        }
+        "date" => matches_pattern(value, date_pattern()),
+        "datetime" => matches_pattern(value, datetime_pattern()),
+        "time" => matches_pattern(value, time_pattern()),
+        "id" | "reference" => matches_pattern(value, id_pattern()),
+        // Compound fields such as addresses are only writable through their component fields
+        "address" | "location" => false,
+        _ => value.is_string() || value.is_number() || value.is_boolean(),
+    };
+
+    if !type_matches {
+        violations.push(FieldViolation::new(
+            name,
+            "TYPE_MISMATCH",
+            format!("{name} expects a value of type {}.", field.field_type),
+        ));
+        return;
+    }
+
+    match value {
+        Value::String(text) => validate_string(field, text, violations),
+        Value::Number(_) | Value::Bool(_) if value_is_text(field) => {
+            validate_string(field, &value.to_string(), violations)
+        }
+        _ => {}
+    }
+}
+
+/// Whether values of the field are stored as text, so scalars sent for it are checked as strings.
+fn value_is_text(field: &FieldDescribe) -> bool {
+    !matches!(
+        field.field_type.as_str(),
+        "boolean" | "int" | "double" | "currency" | "percent"
+    )
+}
+
+fn validate_string(field: &FieldDescribe, text: &str, violations: &mut Vec<FieldViolation>) {
+    let name = field.name.as_str();
+
+    match field.field_type.as_str() {
+        // Unrestricted picklists accept values outside their defined set, so only restricted ones
+        // can be checked locally
+        "picklist" if field.restricted_picklist && !is_active_picklist_value(field, text) => {
+            violations.push(FieldViolation::new(
+                name,
+                "INVALID_PICKLIST_VALUE",
+                format!("{text} is not a valid value for {name}."),
+            ));
+        }
+        "multipicklist" if field.restricted_picklist => {
+            for selection in text.split(';').filter(|selection| !selection.is_empty()) {
+                if !is_active_picklist_value(field, selection) {
+                    violations.push(FieldViolation::new(
+                        name,
+                        "INVALID_PICKLIST_VALUE",
+                        format!("{selection} is not a valid value for {name}."),
+                    ));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let length = text.chars().count();
+    if field.length > 0 && length > field.length as usize {
+        violations.push(FieldViolation::new(
+            name,
+            "STRING_TOO_LONG",
+            format!(
+                "{name} may be at most {} characters, but {length} were provided.",
+                field.length
+            ),
+        ));
+    }
+}
+
+fn is_active_picklist_value(field: &FieldDescribe, value: &str) -> bool {
+    field
+        .picklist_values
+        .iter()
+        .any(|picklist_value| picklist_value.active && picklist_value.value == value)
+}
+
+fn matches_pattern(value: &Value, pattern: &Regex) -> bool {
+    value.as_str().is_some_and(|text| pattern.is_match(text))
+}
+
+fn date_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap())
+}
+
+fn datetime_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?$")
+            .unwrap()
+    })
+}
+
+fn time_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^\d{2}:\d{2}(:\d{2}(\.\d+)?)?Z?$").unwrap())
+}
+
+fn id_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[a-zA-Z0-9]{15}([a-zA-Z0-9]{3})?$").unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn field(name: &str, field_type: &str) -> Value {
+        json!({
+            "name": name,
+            "label": name,
+            "type": field_type,
+            "length": if field_type == "string" { 20 } else { 0 },
+            "precision": 0,
+            "scale": 0,
+            "nillable": true,
+            "createable": true,
+            "updateable": true,
+            "defaultedOnCreate": false,
+            "calculated": false,
+            "custom": name.ends_with("__c"),
+            "unique": false,
+            "externalId": false,
+            "relationshipName": null,
+        })
+    }
+
+    fn lookup(name: &str, relationship_name: &str, nillable: bool) -> Value {
+        let mut lookup = field(name, "reference");
+        lookup["referenceTo"] = json!(["Account"]);
+        lookup["relationshipName"] = json!(relationship_name);
+        lookup["nillable"] = json!(nillable);
+        lookup
+    }
+
+    fn describe() -> SObjectDescribe {
+        serde_json::from_value(json!({
+            "name": "Contact",
+            "label": "Contact",
+            "keyPrefix": "003",
+            "custom": false,
+            "createable": true,
+            "updateable": true,
+            "deletable": true,
+            "queryable": true,
+            "fields": [
+                field("LastName", "string"),
+                field("DoNotCall", "boolean"),
+                field("Employees__c", "int"),
+                field("Salary__c", "currency"),
+                lookup("Partner__c", "Partner__r", true),
+                lookup("AccountId", "Account", false),
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn codes(result: Result<(), Vec<FieldViolation>>) -> Vec<(String, &'static str)> {
+        result
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|violation| (violation.field, violation.code))
+            .collect()
+    }
+
+    #[test]
+    fn strings_salesforce_coerces_are_accepted() {
+        let databag = json!({
+            "DoNotCall": "TRUE",
+            "Employees__c": "42",
+            "Salary__c": "1250.50",
+            "LastName": 42,
+            "AccountId": "001000000000001AAA",
+        });
+
+        assert_eq!(
+            codes(validate_databag(
+                &describe(),
+                &databag,
+                WriteOperation::Update
+            )),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn values_salesforce_refuses_are_rejected() {
+        let databag = json!({
+            "DoNotCall": "yes",
+            "Employees__c": "4.2",
+            "Salary__c": "lots",
+            "AccountId": "not an id",
+        });
+
+        let mut violations = codes(validate_databag(
+            &describe(),
+            &databag,
+            WriteOperation::Update,
+        ));
+        violations.sort();
+
+        assert_eq!(
+            violations,
+            vec![
+                ("AccountId".to_string(), "TYPE_MISMATCH"),
+                ("DoNotCall".to_string(), "TYPE_MISMATCH"),
+                ("Employees__c".to_string(), "TYPE_MISMATCH"),
+                ("Salary__c".to_string(), "TYPE_MISMATCH"),
+            ]
+        );
+    }
+
+    #[test]
+    fn scalars_for_text_fields_are_length_checked() {
+        let databag = json!({ "LastName": 123456789012345678901234_f64 });
+
+        assert_eq!(
+            codes(validate_databag(
+                &describe(),
+                &databag,
+                WriteOperation::Update
+            )),
+            vec![("LastName".to_string(), "STRING_TOO_LONG")]
+        );
+    }
+
+    #[test]
+    fn relationship_external_id_references_are_resolved() {
+        let databag = json!({
+            "LastName": "Lovelace",
+            "Partner__r": { "Ext__c": "P-1" },
+            "account": { "attributes": { "type": "Account" }, "Ext__c": "A-1" },
+        });
+
+        assert_eq!(
+            codes(validate_databag(
+                &describe(),
+                &databag,
+                WriteOperation::Create
+            )),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn malformed_relationship_references_are_rejected() {
+        let databag = json!({
+            "Partner__r": "P-1",
+            "Account": { "Ext__c": "A-1", "Name": "Acme" },
+            "Manager__r": { "Ext__c": "M-1" },
+        });
+
+        let mut violations = codes(validate_databag(
+            &describe(),
+            &databag,
+            WriteOperation::Update,
+        ));
+        violations.sort();
+
+        assert_eq!(
+            violations,
+            vec![
+                ("Account".to_string(), "TYPE_MISMATCH"),
+                ("Manager__r".to_string(), "UNKNOWN_FIELD"),
+                ("Partner__r".to_string(), "TYPE_MISMATCH"),
+            ]
+        );
+    }
+
+    #[test]
+    fn required_lookups_are_missing_without_a_reference() {
+        let databag = json!({ "LastName": "Lovelace" });
+
+        assert_eq!(
+            codes(validate_databag(
+                &describe(),
+                &databag,
+                WriteOperation::Create
+            )),
+            vec![("AccountId".to_string(), "REQUIRED_FIELD_MISSING")]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RateLimitConfiguration {
+    /// Rate at which tokens are added to the bucket.
+    pub requests_per_second: f64,
+    /// Maximum number of tokens the bucket can hold, allowing short bursts above the steady rate.
+    pub burst: u32,
+    /// Maximum number of requests in flight to the organization at once. Salesforce allows 25
+    /// concurrent long-running requests per organization.
+    pub max_concurrent_requests: usize,
+    /// How long a request may wait for a token and a concurrency slot before being turned away.
+    pub queue_timeout_millis: u64,
+}
+
+impl Default for RateLimitConfiguration {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 50.0,
+            burst: 100,
+            max_concurrent_requests: 25,
+            queue_timeout_millis: 5000,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Limits both the rate and the concurrency of requests made to a single organization, queueing
+/// callers until capacity frees up or their queue timeout elapses.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfiguration,
+    bucket: Mutex<TokenBucket>,
+    concurrency: Semaphore,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfiguration) -> Self {
+        Self {
+            config,
+            bucket: Mutex::new(TokenBucket {
+                tokens: config.burst as f64,
+                last_refill: Instant::now(),
+            }),
+            concurrency: Semaphore::new(config.max_concurrent_requests),
+        }
+    }
+
+    /// Waits for a token and a concurrency slot, returning a permit that frees the slot when dropped.
+    /// If neither becomes available within the queue timeout, the suggested time to wait before
+    /// trying again is returned instead.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, Duration> {
+        let deadline = Instant::now() + Duration::from_millis(self.config.queue_timeout_millis);
+
+        loop {
+            match self.try_take_token() {
+                Ok(()) => break,
+                Err(wait) if Instant::now() + wait > deadline => return Err(wait),
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        match tokio::time::timeout(remaining, self.concurrency.acquire()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            // Slots free up as soon as in-flight requests finish, so there's no better estimate
+            _ => Err(Duration::from_secs(1)),
+        }
+    }
+
+    /// Number of requests currently holding a concurrency slot.
+    pub fn in_flight(&self) -> usize {
+        self.config.max_concurrent_requests - self.concurrency.available_permits()
+    }
+
+    fn try_take_token(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+
+        bucket.tokens = (bucket.tokens + elapsed * self.config.requests_per_second)
+            .min(self.config.burst as f64);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let missing = 1.0 - bucket.tokens;
+        Err(Duration::from_secs_f64(
+            missing / self.config.requests_per_second.max(f64::EPSILON),
+        ))
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::salesforce::concurrency::VersionedRecord;
+
+/// Key that applies a time to live to every object that isn't listed on its own.
+const ALL_OBJECTS: &str = "*";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RecordCacheConfiguration {
+    /// Maximum number of records kept, the least recently read are evicted beyond it.
+    pub max_entries: usize,
+    /// How long records are cached for, in seconds, keyed by object name or `*` for every other
+    /// object. Objects without a time to live are always read from Salesforce.
+    pub ttl_seconds: HashMap<String, u64>,
+}
+
+impl Default for RecordCacheConfiguration {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            ttl_seconds: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RecordCacheKey {
+    object: String,
+    id: String,
+    fields: Option<String>,
+}
+
+impl RecordCacheKey {
+    fn new(object: &str, id: &str, fields: Option<&[String]>) -> Self {
+        Self {
+            object: object.to_lowercase(),
+            id: record_id(id).to_string(),
+            fields: fields.map(|fields| {
+                let mut fields: Vec<String> = fields.iter().map(|f| f.to_lowercase()).collect();
+                fields.sort();
+                fields.join(",")
+            }),
+        }
+    }
+}
+
+/// The 15 character form of a record id, which the 18 character form only adds a checksum to.
+fn record_id(id: &str) -> &str {
+    match id.len() {
+        18 if id.is_ascii() => &id[..15],
+        _ => id,
+    }
+}
+
+#[derive(Debug)]
+struct CachedRecord {
+    record: VersionedRecord,
+    expires_at: Instant,
+    used_at: u64,
+}
+
+/// A record regardless of the fields it was read with, as invalidated by an update.
+type RecordId = (String, String);
+
+#[derive(Debug, Default)]
+struct Entries {
+    records: HashMap<RecordCacheKey, CachedRecord>,
+    /// Keys ordered by when they were last read, least recent first.
+    recency: BTreeMap<u64, RecordCacheKey>,
+    clock: u64,
+    /// The generation each recently invalidated record was given, from a counter shared by all of
+    /// them, so reads that raced an invalidation can be told apart from later ones.
+    generations: HashMap<RecordId, u64>,
+    /// Records by the generation they were given, oldest first, so the least recent can be evicted.
+    invalidations: BTreeMap<u64, RecordId>,
+    invalidation_clock: u64,
+    /// The generation of records without one of their own, raised to that of any evicted record so
+    /// reads that began before its invalidation still aren't cached.
+    generation_floor: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &RecordCacheKey) {
+        self.clock += 1;
+        if let Some(cached) = self.records.get_mut(key) {
+            self.recency.remove(&cached.used_at);
+            cached.used_at = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &RecordCacheKey) {
+        if let Some(cached) = self.records.remove(key) {
+            self.recency.remove(&cached.used_at);
+        }
+    }
+
+    fn generation(&self, record: &RecordId) -> u64 {
+        self.generations
+            .get(record)
+            .copied()
+            .unwrap_or(self.generation_floor)
+    }
+
+    fn advance_generation(&mut self, record: RecordId, max_entries: usize) {
+        self.invalidation_clock += 1;
+        if let Some(previous) = self
+            .generations
+            .insert(record.clone(), self.invalidation_clock)
+        {
+            self.invalidations.remove(&previous);
+        }
+        self.invalidations.insert(self.invalidation_clock, record);
+
+        while self.generations.len() > max_entries {
+            match self.invalidations.pop_first() {
+                Some((generation, evicted)) => {
+                    self.generations.remove(&evicted);
+                    self.generation_floor = self.generation_floor.max(generation);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Least recently used cache of the records read from a single organization, for objects with a
+/// configured time to live.
+#[derive(Debug)]
+pub struct RecordCache {
+    max_entries: usize,
+    ttls: HashMap<String, Duration>,
+    entries: Mutex<Entries>,
+}
+
+impl RecordCache {
+    pub fn new(config: &RecordCacheConfiguration) -> Self {
+        Self {
+            max_entries: config.max_entries,
+            ttls: config
+                .ttl_seconds
+                .iter()
+                .map(|(object, seconds)| (object.to_lowercase(), Duration::from_secs(*seconds)))
+                .collect(),
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// How long records of the object are cached for, if at all.
+    pub fn ttl(&self, object: &str) -> Option<Duration> {
+        self.ttls
+            .get(&object.to_lowercase())
+            .or_else(|| self.ttls.get(ALL_OBJECTS))
+            .copied()
+            .filter(|ttl| !ttl.is_zero() && self.max_entries > 0)
+    }
+
+    /// The cached record, with `max_age` set to how much longer it remains fresh.
+    pub fn get(
+        &self,
+        object: &str,
+        id: &str,
+        fields: Option<&[String]>,
+    ) -> Option<VersionedRecord> {
+        let key = RecordCacheKey::new(object, id, fields);
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+
+        let (record, expires_at) = match entries.records.get(&key) {
+            Some(cached) if cached.expires_at > now => (cached.record.clone(), cached.expires_at),
+            Some(_) => {
+                entries.remove(&key);
+                return None;
+            }
+            None => return None,
+        };
+        entries.touch(&key);
+
+        Some(VersionedRecord {
+            max_age: Some(expires_at - now),
+            ..record
+        })
+    }
+
+    /// The record's generation, taken before reading it from Salesforce and passed on to
+    /// [`Self::insert`] so a read that raced an update isn't cached after the update invalidated it.
+    pub fn generation(&self, object: &str, id: &str) -> u64 {
+        let record = (object.to_lowercase(), record_id(id).to_string());
+        self.entries.lock().unwrap().generation(&record)
+    }
+
+    /// Caches a record read at the given generation, unless it has been invalidated since.
+    pub fn insert(
+        &self,
+        object: &str,
+        id: &str,
+        fields: Option<&[String]>,
+        record: &VersionedRecord,
+        generation: u64,
+    ) {
+        let Some(ttl) = self.ttl(object) else {
+            return;
+        };
+
+        let key = RecordCacheKey::new(object, id, fields);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation(&(key.object.clone(), key.id.clone())) != generation {
+            return;
+        }
+        entries.remove(&key);
+
+        while entries.records.len() >= self.max_entries {
+            match entries.recency.pop_first() {
+                Some((_, evicted)) => {
+                    entries.records.remove(&evicted);
+                }
+                None => break,
+            }
+        }
+
+        entries.records.insert(
+            key.clone(),
+            CachedRecord {
+                record: record.clone(),
+                expires_at: Instant::now() + ttl,
+                used_at: 0,
+            },
+        );
+        entries.touch(&key);
+    }
+
+    /// Drops every cached read of the record, whichever fields it was read with, and keeps reads
+    /// already in progress from caching what they return.
+    pub fn invalidate(&self, object: &str, id: &str) {
+        let object = object.to_lowercase();
+        let id = record_id(id);
+        let mut entries = self.entries.lock().unwrap();
+        entries.advance_generation((object.clone(), id.to_string()), self.max_entries);
+
+        let stale: Vec<RecordCacheKey> = entries
+            .records
+            .keys()
+            .filter(|key| key.object == object && key.id == id)
+            .cloned()
+            .collect();
+        for key in &stale {
+            entries.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::salesforce::concurrency::RecordVersion;
+
+    const ID: &str = "001000000000001AAA";
+
+    fn cache(max_entries: usize) -> RecordCache {
+        RecordCache::new(&RecordCacheConfiguration {
+            max_entries,
+            ttl_seconds: HashMap::from([("Account".to_string(), 60)]),
+        })
+    }
+
+    fn record(name: &str) -> VersionedRecord {
+        VersionedRecord {
+            record: json!({ "Id": ID, "Name": name }),
+            version: RecordVersion::default(),
+            max_age: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_that_raced_an_invalidation_are_not_cached() {
+        let cache = cache(10);
+
+        let generation = cache.generation("Account", ID);
+        cache.invalidate("account", &ID[..15]);
+        cache.insert("Account", ID, None, &record("Before"), generation);
+        assert!(cache.get("Account", ID, None).is_none());
+
+        let generation = cache.generation("Account", ID);
+        cache.insert("Account", ID, None, &record("After"), generation);
+        assert_eq!(
+            cache.get("Account", ID, None).unwrap().record["Name"],
+            "After"
+        );
+    }
+
+    #[tokio::test]
+    async fn evicted_generations_still_reject_racing_reads() {
+        let cache = cache(1);
+
+        let generation = cache.generation("Account", ID);
+        cache.invalidate("Account", ID);
+        // Invalidating another record evicts the first one's generation
+        cache.invalidate("Account", "001000000000002AAA");
+        cache.insert("Account", ID, None, &record("Before"), generation);
+
+        assert!(cache.get("Account", ID, None).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::config::AggregateSystemConfiguration;
+use crate::config::{AggregateSystemConfiguration, ServiceConfiguration};
 use crate::organization::SalesforceOrganization;
 use crate::salesforce::service::SalesforceService;
 
@@ -9,6 +9,7 @@ pub struct SalesforceServiceResolver {
     uw_service: Arc<SalesforceService>,
     nf_service: Arc<SalesforceService>,
     qb_service: Arc<SalesforceService>,
+    service_configuration: ServiceConfiguration,
 }
 
 impl SalesforceServiceResolver {
@@ -17,20 +18,36 @@ impl SalesforceServiceResolver {
 
         Self {
             uw_service: Arc::new(SalesforceService::new(
+                SalesforceOrganization::Underwriting,
                 aggregate_system_configuration.uw_salesforce_config,
                 service_configuration.clone(),
             )),
             nf_service: Arc::new(SalesforceService::new(
+                SalesforceOrganization::NationalFunding,
                 aggregate_system_configuration.nf_salesforce_config,
                 service_configuration.clone(),
             )),
             qb_service: Arc::new(SalesforceService::new(
+                SalesforceOrganization::QuickBridge,
                 aggregate_system_configuration.qb_salesforce_config,
-                service_configuration,
+                service_configuration.clone(),
             )),
+            service_configuration,
         }
     }
 
+    pub fn service_configuration(&self) -> &ServiceConfiguration {
+        &self.service_configuration
+    }
+
+    /// Every organization along with the service used to reach it.
+    pub fn services(&self) -> Vec<(SalesforceOrganization, Arc<SalesforceService>)> {
+        SalesforceOrganization::ALL
+            .iter()
+            .map(|organization| (*organization, self.resolve(*organization)))
+            .collect()
+    }
+
     pub fn resolve(&self, organization: SalesforceOrganization) -> Arc<SalesforceService> {
         match organization {
             SalesforceOrganization::NationalFunding => self.nf_service.clone(),
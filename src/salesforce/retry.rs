@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::errors::ServiceError;
+use crate::salesforce::operation::SalesforceOperation;
+
+/// Error codes Salesforce reports when a request was not processed at all, which are safe to retry
+/// for any operation.
+const RETRYABLE_ERROR_CODES: [&str; 2] = ["SERVER_UNAVAILABLE", "UNABLE_TO_LOCK_ROW"];
+
+/// Upstream statuses indicating a transient failure where the request may or may not have been
+/// processed, only safe to retry for idempotent operations.
+const RETRYABLE_STATUSES: [u16; 3] = [502, 503, 504];
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RetryPolicy {
+    /// Total number of attempts including the first, a value of one disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff_millis: u64,
+    pub max_backoff_millis: u64,
+    pub backoff_multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_millis: 200,
+            max_backoff_millis: 5000,
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Determines if a failed attempt should be retried, given the number of attempts made so far.
+    pub fn should_retry(
+        &self,
+        operation: SalesforceOperation,
+        attempt: u32,
+        error: &ServiceError,
+    ) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+
+        match error {
+            // A failed connection means the request never reached Salesforce
+            ServiceError::ClientRequestFailed(e) if e.is_connect() => true,
+            ServiceError::ClientRequestFailed(e) if e.is_timeout() || e.is_request() => {
+                operation.is_idempotent()
+            }
+            ServiceError::SalesforceRequestFailed(status, errors) => {
+                let retryable_code = errors
+                    .iter()
+                    .any(|error| RETRYABLE_ERROR_CODES.contains(&error.error_code.as_str()));
+
+                retryable_code || (operation.is_idempotent() && RETRYABLE_STATUSES.contains(status))
+            }
+            _ => false,
+        }
+    }
+
+    /// Exponential backoff with full jitter, so retries from concurrent requests don't arrive in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let ceiling = (self.initial_backoff_millis as f64 * self.backoff_multiplier.powi(exponent))
+            .min(self.max_backoff_millis as f64);
+        let jittered = rand::thread_rng().gen_range(0.0..=ceiling.max(0.0));
+
+        Duration::from_millis(jittered as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_stays_below_a_growing_capped_ceiling() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff_millis: 100,
+            max_backoff_millis: 300,
+            backoff_multiplier: 2.0,
+        };
+
+        for _ in 0..200 {
+            assert!(policy.backoff(1) <= Duration::from_millis(100));
+            assert!(policy.backoff(2) <= Duration::from_millis(200));
+            assert!(policy.backoff(3) <= Duration::from_millis(300));
+            assert!(policy.backoff(10) <= Duration::from_millis(300));
+        }
+    }
+}
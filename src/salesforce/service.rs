@@ -1,43 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::ops::Add;
+use std::sync::Arc;
 use std::time::Duration;
 
+use futures::stream::{self, Stream};
 use regex::Regex;
-use reqwest::StatusCode;
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, DATE, IF_MODIFIED_SINCE, LAST_MODIFIED,
+};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::Value;
 use time::OffsetDateTime;
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio::time::Instant;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 use crate::config::{SalesforceConfiguration, ServiceConfiguration};
-use crate::errors::{ServiceError, ServiceResult};
+use crate::errors::{SalesforceApiError, ServiceError, ServiceResult, NOT_FOUND_ERROR_CODE};
+use crate::metrics::metrics;
+use crate::organization::SalesforceOrganization;
+use crate::redaction::redactor;
+use crate::request_context::record_salesforce_call;
+use crate::salesforce::bulk::{
+    BulkJobState, BulkQueryJob, BulkQueryResultChunk, CreateBulkQueryJobRequest,
+    SFORCE_LOCATOR_HEADER, SFORCE_NUMBER_OF_RECORDS_HEADER,
+};
+use crate::salesforce::changes::{diff_record, FieldChange};
+use crate::salesforce::circuit_breaker::{CircuitBreaker, CircuitSnapshot};
+use crate::salesforce::concurrency::{RecordVersion, VersionedRecord, WritePreconditions};
+use crate::salesforce::conversion::{csv_columns, restrict_csv_columns};
+use crate::salesforce::cursor::RelatedRecordsCursors;
+use crate::salesforce::describe::{CachedDescribe, GlobalDescribe, Relationship, SObjectDescribe};
+use crate::salesforce::field_policy::{FieldPolicies, ReadAccess, ALL_OBJECTS};
+use crate::salesforce::limits::{
+    ApiLimitDecision, ApiUsage, ApiUsageTracker, SFORCE_LIMIT_INFO_HEADER,
+};
+use crate::salesforce::operation::SalesforceOperation;
+use crate::salesforce::preflight::{validate_databag, validate_field_selection, WriteOperation};
+use crate::salesforce::rate_limit::RateLimiter;
+use crate::salesforce::record_cache::RecordCache;
+use crate::salesforce::retry::RetryPolicy;
+use crate::soql::SoqlQuery;
+use crate::telemetry::inject_context;
+
+const DEFAULT_SALESFORCE_VERSION: &str = "59.0";
+
+const DEFAULT_DESCRIBE_CACHE_SECONDS: u64 = 300;
+
+/// How an update is carried out, beyond the values being written.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateOptions {
+    pub preconditions: WritePreconditions,
+    /// Fetches the current values of the fields being written first, so the changes the update
+    /// made can be reported.
+    pub capture_changes: bool,
+}
+
+/// Records reached by following a relationship from a record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelatedRecords {
+    /// The record a lookup references.
+    Parent { object: String, record: Value },
+    /// A page of child records, with the cursor of the next page if there is one.
+    Children {
+        object: String,
+        page: Value,
+        next_cursor: Option<String>,
+    },
+}
+
+impl RelatedRecords {
+    /// The object of the related records.
+    pub fn object(&self) -> &str {
+        match self {
+            Self::Parent { object, .. } | Self::Children { object, .. } => object,
+        }
+    }
+}
 
-#[derive(Debug)]
 pub struct SalesforceService {
+    organization: SalesforceOrganization,
     http: reqwest::Client,
     config: SalesforceConfiguration,
+    retry_policy: RetryPolicy,
+    circuit_breaker: CircuitBreaker,
+    api_usage: ApiUsageTracker,
+    rate_limiter: RateLimiter,
+    field_policies: FieldPolicies,
+    record_cache: RecordCache,
+    related_cursors: RelatedRecordsCursors,
+    api_version: String,
     access_token: Mutex<Option<String>>,
     instance_url: Mutex<Option<String>>,
     expires_at: Mutex<OffsetDateTime>,
+    describe_cache_duration: Duration,
+    describe_cache: Mutex<HashMap<String, CachedDescribe<SObjectDescribe>>>,
+    global_describe_cache: Mutex<Option<CachedDescribe<GlobalDescribe>>>,
+}
+
+// Written by hand so the credentials and access token never end up in logs or spans
+impl fmt::Debug for SalesforceService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SalesforceService")
+            .field("organization", &self.organization)
+            .field("api_version", &self.api_version)
+            .finish_non_exhaustive()
+    }
 }
 
 impl SalesforceService {
     pub fn new(
+        organization: SalesforceOrganization,
         salesforce_configuration: SalesforceConfiguration,
         service_configuration: ServiceConfiguration,
     ) -> Self {
+        let organization_configuration = service_configuration.organization(organization);
         let timeout_duration =
             Duration::from_secs(service_configuration.timeout_seconds.unwrap_or(5));
         let client = reqwest::ClientBuilder::new()
             .timeout(timeout_duration)
             .build()
             .unwrap();
+        let api_version = service_configuration
+            .salesforce_version
+            .as_deref()
+            .unwrap_or(DEFAULT_SALESFORCE_VERSION)
+            .trim_start_matches('v')
+            .to_string();
+        let describe_cache_duration = Duration::from_secs(
+            service_configuration
+                .describe_cache_seconds
+                .unwrap_or(DEFAULT_DESCRIBE_CACHE_SECONDS),
+        );
+
+        let related_cursors = RelatedRecordsCursors::new(&salesforce_configuration.consumer_secret);
+
         Self {
+            organization,
             http: client,
             config: salesforce_configuration,
+            retry_policy: organization_configuration.retry_policy,
+            circuit_breaker: CircuitBreaker::new(organization_configuration.circuit_breaker),
+            api_usage: ApiUsageTracker::new(organization_configuration.api_limits),
+            rate_limiter: RateLimiter::new(organization_configuration.rate_limit),
+            field_policies: FieldPolicies::new(&organization_configuration.field_policies),
+            record_cache: RecordCache::new(&organization_configuration.record_cache),
+            related_cursors,
+            api_version,
             access_token: Mutex::new(None),
             instance_url: Mutex::new(None),
             expires_at: Mutex::new(OffsetDateTime::UNIX_EPOCH),
+            describe_cache_duration,
+            describe_cache: Mutex::new(HashMap::new()),
+            global_describe_cache: Mutex::new(None),
         }
     }
 
@@ -70,6 +188,255 @@ impl SalesforceService {
         }
     }
 
+    fn try_instance_url(&self) -> ServiceResult<String> {
+        match self.instance_url.try_lock() {
+            Ok(lock) => match lock.as_ref() {
+                None => Err(ServiceError::InstanceUrlNotFound),
+                Some(instance_url) => Ok(instance_url.to_owned()),
+            },
+            Err(e) => Err(ServiceError::ObjectRetrievalFailed(e.to_string())),
+        }
+    }
+
+    /// Builds a URL to a REST resource under the configured Salesforce API version.
+    fn versioned_url(&self, path: &str) -> ServiceResult<String> {
+        let instance_url = self.try_instance_url()?;
+        Ok(format!(
+            "{instance_url}/services/data/v{}/{path}",
+            self.api_version
+        ))
+    }
+
+    pub fn organization(&self) -> SalesforceOrganization {
+        self.organization
+    }
+
+    pub fn circuit_state(&self) -> CircuitSnapshot {
+        self.circuit_breaker.snapshot()
+    }
+
+    /// The organization's API usage as last reported by Salesforce.
+    pub fn api_usage(&self) -> Option<ApiUsage> {
+        self.api_usage.usage()
+    }
+
+    /// Sends a request to Salesforce, retrying transient failures according to the organization's
+    /// retry policy, queueing behind its rate and concurrency limits, and failing fast while the
+    /// organization's circuit is open. Requests that cannot be cloned, such as those with streaming
+    /// bodies, are only attempted once. Requests rejected because the access token expired early
+    /// or was revoked are sent once more with a new one.
+    async fn send(
+        &self,
+        operation: SalesforceOperation,
+        object: Option<&str>,
+        request: RequestBuilder,
+    ) -> ServiceResult<Response> {
+        match self.api_usage.decide(operation) {
+            ApiLimitDecision::Proceed => {}
+            ApiLimitDecision::Delay(delay) => {
+                warn!(
+                    "{} is close to its API limit, delaying {} by {}ms",
+                    self.organization,
+                    operation.as_str(),
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            ApiLimitDecision::Reject(usage) => {
+                warn!(
+                    "{} is close to its API limit, rejecting {}",
+                    self.organization,
+                    operation.as_str()
+                );
+                return Err(ServiceError::ApiLimitThresholdExceeded(
+                    self.organization,
+                    usage,
+                ));
+            }
+        }
+
+        let mut request = request;
+        let mut attempt = 1;
+        let mut session_renewed = false;
+
+        loop {
+            // Held until the attempt completes, freeing the concurrency slot for queued requests
+            let permit = match self.rate_limiter.acquire().await {
+                Ok(permit) => permit,
+                Err(retry_after) => {
+                    warn!(
+                        "{} has {} requests in flight, turning away {}",
+                        self.organization,
+                        self.rate_limiter.in_flight(),
+                        operation.as_str()
+                    );
+                    return Err(ServiceError::RateLimited(
+                        self.organization,
+                        retry_after.as_secs().max(1),
+                    ));
+                }
+            };
+
+            let circuit_permit = match self.circuit_breaker.try_acquire() {
+                Ok(circuit_permit) => circuit_permit,
+                Err(retry_after) => {
+                    warn!(
+                        "Circuit for {} is open, failing {} fast",
+                        self.organization,
+                        operation.as_str()
+                    );
+                    return Err(ServiceError::OrganizationUnavailable(
+                        self.organization,
+                        retry_after.as_secs().max(1),
+                    ));
+                }
+            };
+
+            let retry_request = request.try_clone();
+            let span = info_span!(
+                "salesforce_request",
+                otel.kind = "client",
+                organization = %self.organization,
+                operation = operation.as_str(),
+                object,
+                attempt,
+                http.status_code = tracing::field::Empty,
+            );
+            record_salesforce_call();
+            let started_at = Instant::now();
+            let result = self.send_once(request).instrument(span.clone()).await;
+            let status = response_status(&result);
+            span.record("http.status_code", status.as_str());
+            self.record_call(operation, started_at, &status);
+            circuit_permit.record(!CircuitBreaker::is_failure(&result));
+
+            match (result, retry_request) {
+                (Err(e), Some(retry_request)) if !session_renewed && is_invalid_session(&e) => {
+                    drop(permit);
+                    warn!(
+                        "Access token for {} was rejected, renewing it to retry {}",
+                        self.organization,
+                        operation.as_str()
+                    );
+                    *self.access_token.lock().await = None;
+                    let access_token = Box::pin(self.get_access_token()).await?;
+
+                    let mut authorization = HeaderMap::new();
+                    authorization.insert(
+                        AUTHORIZATION,
+                        HeaderValue::from_str(&format!("Bearer {access_token}"))
+                            .map_err(|_| ServiceError::AuthenticationFailed)?,
+                    );
+                    request = retry_request.headers(authorization);
+                    session_renewed = true;
+                }
+                (Err(e), Some(retry_request))
+                    if self.retry_policy.should_retry(operation, attempt, &e) =>
+                {
+                    // Queued requests can use the slot while this one waits to retry
+                    drop(permit);
+                    let backoff = self.retry_policy.backoff(attempt);
+                    warn!(
+                        "Attempt {attempt} of {} to {} failed, retrying in {}ms: {}",
+                        self.retry_policy.max_attempts,
+                        operation.as_str(),
+                        backoff.as_millis(),
+                        redactor().redact_text(&e.to_string())
+                    );
+                    tokio::time::sleep(backoff).await;
+                    request = retry_request;
+                    attempt += 1;
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
+    fn record_call(&self, operation: SalesforceOperation, started_at: Instant, status: &str) {
+        metrics()
+            .salesforce_requests_total
+            .with_label_values(&[self.organization.as_str(), operation.as_str(), status])
+            .inc();
+        metrics()
+            .salesforce_request_duration_seconds
+            .with_label_values(&[self.organization.as_str(), operation.as_str()])
+            .observe(started_at.elapsed().as_secs_f64());
+    }
+
+    fn record_api_usage(&self, usage: ApiUsage) {
+        self.api_usage.record(usage);
+
+        let organization = self.organization.as_str();
+        metrics()
+            .api_usage
+            .with_label_values(&[organization, "used"])
+            .set(usage.used as i64);
+        metrics()
+            .api_usage
+            .with_label_values(&[organization, "max"])
+            .set(usage.max as i64);
+    }
+
+    /// Makes a single attempt at a request, turning any unsuccessful response into the matching
+    /// [`ServiceError`] using the error codes found in its body.
+    async fn send_once(&self, request: RequestBuilder) -> ServiceResult<Response> {
+        let mut trace_headers = HeaderMap::new();
+        inject_context(&mut trace_headers);
+
+        let response = request.headers(trace_headers).send().await?;
+        let status = response.status();
+
+        let usage = response
+            .headers()
+            .get(SFORCE_LIMIT_INFO_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(ApiUsage::parse);
+
+        if let Some(usage) = usage {
+            self.record_api_usage(usage);
+        }
+
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+
+        let body = response.text().await?;
+        let errors = SalesforceApiError::parse(status, &body);
+
+        error!(
+            "Salesforce responded with {status}: {}",
+            errors
+                .iter()
+                .map(|error| error.error_code.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let not_found = status == StatusCode::NOT_FOUND
+            && errors
+                .iter()
+                .all(|error| error.error_code == NOT_FOUND_ERROR_CODE);
+
+        if not_found {
+            return Err(ServiceError::ObjectNotFound);
+        }
+
+        if status == StatusCode::PRECONDITION_FAILED {
+            return Err(ServiceError::PreconditionFailed);
+        }
+
+        Err(ServiceError::SalesforceRequestFailed(
+            status.as_u16(),
+            errors,
+        ))
+    }
+
+    /// Ensures the service holds a valid access token, requesting a new one if needed.
+    pub async fn authenticate(&self) -> ServiceResult<()> {
+        self.get_access_token().await?;
+        Ok(())
+    }
+
     async fn get_access_token(&self) -> ServiceResult<String> {
         // If we have a cached access token, go ahead and grab it as it hasn't hit the expired time yet
         if let Ok(Some(cached_token)) = self.try_access_token() {
@@ -79,22 +446,25 @@ impl SalesforceService {
 
         info!("No cached access token found, requesting a new one from Salesforce");
 
-        let form = reqwest::multipart::Form::new()
-            .text("grant_type", "password")
-            .text("client_id", self.config.consumer_key.clone())
-            .text("client_secret", self.config.consumer_secret.clone())
-            .text("username", self.config.user_name.clone())
-            .text("password", self.config.password.clone());
+        // Sent as a URL encoded form rather than multipart so the request can be cloned for retries
+        let form = [
+            ("grant_type", "password"),
+            ("client_id", self.config.consumer_key.as_str()),
+            ("client_secret", self.config.consumer_secret.as_str()),
+            ("username", self.config.user_name.as_str()),
+            ("password", self.config.password.as_str()),
+        ];
 
-        let token_response = self
-            .http
-            .post(self.config.salesforce_url.clone())
-            .multipart(form)
-            .send()
-            .await?
-            .json::<AccessTokenResponse>()
-            .await?;
-        dbg!(&token_response);
+        let token_response = match self.request_access_token(&form).await {
+            Ok(token_response) => {
+                self.record_token_refresh("success");
+                token_response
+            }
+            Err(e) => {
+                self.record_token_refresh("failure");
+                return Err(e);
+            }
+        };
         let access_token = token_response.access_token;
         let instance_url = token_response.instance_url;
 
@@ -114,55 +484,299 @@ impl SalesforceService {
         Ok(access_token)
     }
 
+    async fn request_access_token(
+        &self,
+        form: &[(&str, &str)],
+    ) -> ServiceResult<AccessTokenResponse> {
+        let result = match self
+            .send(
+                SalesforceOperation::Authenticate,
+                None,
+                self.http
+                    .post(self.config.salesforce_url.clone())
+                    .form(form),
+            )
+            .await
+        {
+            Ok(response) => response
+                .json::<AccessTokenResponse>()
+                .await
+                .map_err(ServiceError::from),
+            Err(e) => Err(e),
+        };
+
+        // Token endpoint failures describe the service's own credentials, so callers only learn
+        // that Salesforce could not be reached on their behalf
+        result.map_err(|e| match e {
+            ServiceError::SalesforceRequestFailed(..)
+            | ServiceError::ClientRequestFailed(_)
+            | ServiceError::ObjectNotFound
+            | ServiceError::PreconditionFailed => {
+                error!("Requesting an access token from Salesforce failed: {e}");
+                ServiceError::AuthenticationFailed
+            }
+            e => e,
+        })
+    }
+
+    fn record_cache_lookup(&self, outcome: &str) {
+        metrics()
+            .record_cache_lookups_total
+            .with_label_values(&[self.organization.as_str(), outcome])
+            .inc();
+    }
+
+    fn record_token_refresh(&self, outcome: &str) {
+        metrics()
+            .token_refreshes_total
+            .with_label_values(&[self.organization.as_str(), outcome])
+            .inc();
+    }
+
     pub async fn get_object_by_id(&self, object: String, id: String) -> ServiceResult<Value> {
+        Ok(self.get_cached_record(&object, &id, None).await?.record)
+    }
+
+    /// Retrieves only the given fields of a record, which must all exist on the object.
+    pub async fn get_object_fields(
+        &self,
+        object: String,
+        id: String,
+        fields: Vec<String>,
+    ) -> ServiceResult<Value> {
+        let record = self.get_versioned_object(object, id, Some(fields)).await?;
+
+        Ok(record.record)
+    }
+
+    /// Retrieves a record along with its version, which can be passed back as preconditions of an
+    /// update to guard against overwriting changes made since. When fields are given only those are
+    /// retrieved, and they must all exist on the object.
+    pub async fn get_versioned_object(
+        &self,
+        object: String,
+        id: String,
+        fields: Option<Vec<String>>,
+    ) -> ServiceResult<VersionedRecord> {
+        if let Some(fields) = &fields {
+            self.validate_field_selection(&object, fields).await?;
+        }
+
+        self.get_cached_record(&object, &id, fields.as_deref())
+            .await
+    }
+
+    async fn validate_field_selection(&self, object: &str, fields: &[String]) -> ServiceResult<()> {
+        let describe = self.describe_object(object.to_string()).await?;
+
+        validate_field_selection(&describe, fields).map_err(|violations| {
+            ServiceError::FieldSelectionInvalid(object.to_string(), violations)
+        })
+    }
+
+    /// Retrieves a record with the field policies applied, limited to the given fields if any.
+    async fn get_record(
+        &self,
+        object: &str,
+        id: &str,
+        fields: Option<&[String]>,
+    ) -> ServiceResult<VersionedRecord> {
         let access_token = self.get_access_token().await?;
+        let url = self.versioned_url(&format!("sobjects/{object}/{id}"))?;
+        let mut request = self.http.get(&url).bearer_auth(access_token);
+        if let Some(fields) = fields {
+            request = request.query(&[("fields", fields.join(","))]);
+        }
 
-        match self.instance_url.try_lock() {
-            Ok(lock) => match lock.as_ref() {
-                None => Err(ServiceError::InstanceUrlNotFound),
-                Some(instance_url) => {
-                    let url = format!("{instance_url}/services/data/v59.0/sobjects/{object}/{id}");
-                    let response = self.http.get(&url).bearer_auth(access_token).send().await?;
+        let response = self
+            .send(SalesforceOperation::GetRecord, Some(object), request)
+            .await?;
+        let headers = response.headers().clone();
+        let mut record = response.json::<Value>().await?;
+        let version = RecordVersion::from_response(&headers, &record);
+        self.field_policies
+            .apply_to_record(Some(object), &mut record);
 
-                    if response.status() == StatusCode::NOT_FOUND {
-                        return Err(ServiceError::ObjectNotFound);
-                    }
+        Ok(VersionedRecord {
+            record,
+            version,
+            max_age: None,
+        })
+    }
 
-                    let object = response.json::<Value>().await?;
+    /// Retrieves a record like [`Self::get_record`], reusing an earlier read of it when its object
+    /// is cached.
+    async fn get_cached_record(
+        &self,
+        object: &str,
+        id: &str,
+        fields: Option<&[String]>,
+    ) -> ServiceResult<VersionedRecord> {
+        let Some(ttl) = self.record_cache.ttl(object) else {
+            return self.get_record(object, id, fields).await;
+        };
 
-                    Ok(object)
-                }
-            },
-            Err(e) => Err(ServiceError::ObjectRetrievalFailed(e.to_string())),
+        if let Some(record) = self.record_cache.get(object, id, fields) {
+            self.record_cache_lookup("hit");
+            return Ok(record);
         }
+        self.record_cache_lookup("miss");
+
+        let generation = self.record_cache.generation(object, id);
+        let record = self.get_record(object, id, fields).await?;
+        self.record_cache
+            .insert(object, id, fields, &record, generation);
+
+        Ok(VersionedRecord {
+            max_age: Some(ttl),
+            ..record
+        })
     }
 
-    pub async fn get_objects(&self, soql: String) -> ServiceResult<Value> {
-        let access_token = self.get_access_token().await?;
+    /// The objects a relationship from the object leads to, which is several for polymorphic
+    /// lookups, so access to them can be checked before following it.
+    pub async fn related_objects(
+        &self,
+        object: &str,
+        relationship: &str,
+    ) -> ServiceResult<Vec<String>> {
+        let describe = self.describe_object(object.to_string()).await?;
 
-        match self.instance_url.try_lock() {
-            Ok(lock) => match lock.as_ref() {
-                None => Err(ServiceError::InstanceUrlNotFound),
-                Some(instance_url) => {
-                    let regex = Regex::new(r"\s+").unwrap();
-                    let updated_soql = regex.replace_all(&soql, " ").to_string();
+        match describe.relationship(relationship) {
+            Some(Relationship::Lookup { objects, .. }) => Ok(objects),
+            Some(Relationship::Child { object, .. }) => Ok(vec![object]),
+            None => Err(ServiceError::RelationshipNotFound(
+                object.to_string(),
+                relationship.to_string(),
+            )),
+        }
+    }
 
-                    info!("Executing adjusted SOQL query:\n{updated_soql}");
+    /// Follows a relationship from a record, retrieving the record a lookup references or a page of
+    /// the child records, continuing from `cursor` when provided. When fields are given only those
+    /// are retrieved, and they must all exist on the related object.
+    pub async fn get_related_records(
+        &self,
+        object: String,
+        id: String,
+        relationship: String,
+        fields: Option<Vec<String>>,
+        cursor: Option<String>,
+    ) -> ServiceResult<RelatedRecords> {
+        let describe = self.describe_object(object.clone()).await?;
+        let Some(relationship) = describe.relationship(&relationship) else {
+            return Err(ServiceError::RelationshipNotFound(object, relationship));
+        };
 
-                    let url = format!(
-                        "{}/services/data/v59.0/query/?q={}",
-                        instance_url,
-                        updated_soql.replace(' ', "+")
-                    );
+        let related_objects = match &relationship {
+            Relationship::Lookup { objects, .. } => objects.as_slice(),
+            Relationship::Child { object, .. } => std::slice::from_ref(object),
+        };
+        // Polymorphic lookups are left for Salesforce to validate
+        if let (Some(fields), [related_object]) = (&fields, related_objects) {
+            self.validate_field_selection(related_object, fields)
+                .await?;
+        }
 
-                    let response = self.http.get(&url).bearer_auth(access_token).send().await?;
-                    let objects = response.json::<Value>().await?;
+        let access_token = self.get_access_token().await?;
+        let url = match (&relationship, cursor) {
+            (Relationship::Child { name, .. }, Some(cursor)) => {
+                let locator = self.related_cursors.decode(&object, &id, name, &cursor)?;
+                self.versioned_url(&format!("query/{locator}"))?
+            }
+            (_, Some(_)) => return Err(ServiceError::CursorInvalid),
+            (Relationship::Lookup { name, .. } | Relationship::Child { name, .. }, None) => {
+                self.versioned_url(&format!("sobjects/{object}/{id}/{name}"))?
+            }
+        };
+        let mut request = self.http.get(&url).bearer_auth(access_token);
+        if let Some(fields) = &fields {
+            request = request.query(&[("fields", fields.join(","))]);
+        }
 
-                    Ok(objects)
-                }
+        let result = self
+            .send(
+                SalesforceOperation::GetRelatedRecords,
+                Some(&object),
+                request,
+            )
+            .await;
+
+        let response = match (result, &relationship) {
+            // Salesforce doesn't tell an empty lookup apart from a missing record
+            (Err(ServiceError::ObjectNotFound), Relationship::Lookup { name, field, .. }) => {
+                self.get_record(&object, &id, Some(std::slice::from_ref(field)))
+                    .await?;
+                return Err(ServiceError::RelatedRecordNotFound(
+                    object,
+                    id,
+                    name.clone(),
+                ));
+            }
+            (result, _) => result?,
+        };
+        let mut related = response.json::<Value>().await?;
+        self.field_policies.apply_to_record(None, &mut related);
+
+        let related = match relationship {
+            Relationship::Lookup { objects, .. } => RelatedRecords::Parent {
+                object: related
+                    .pointer("/attributes/type")
+                    .and_then(Value::as_str)
+                    .or(objects.first().map(String::as_str))
+                    .unwrap_or_default()
+                    .to_string(),
+                record: related,
             },
-            Err(e) => Err(ServiceError::ObjectRetrievalFailed(e.to_string())),
-        }
+            Relationship::Child {
+                object: child_object,
+                name,
+            } => {
+                let next_cursor = related
+                    .as_object_mut()
+                    .and_then(|page| page.remove("nextRecordsUrl"))
+                    .as_ref()
+                    .and_then(Value::as_str)
+                    .and_then(|url| url.rsplit_once("/query/"))
+                    .map(|(_, locator)| self.related_cursors.encode(&object, &id, &name, locator));
+
+                RelatedRecords::Children {
+                    object: child_object,
+                    page: related,
+                    next_cursor,
+                }
+            }
+        };
+
+        Ok(related)
+    }
+
+    pub async fn get_objects(&self, soql: String) -> ServiceResult<Value> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url("query/")?;
+
+        let regex = Regex::new(r"\s+").unwrap();
+        let updated_soql = regex.replace_all(&soql, " ").to_string();
+
+        info!(
+            "Executing adjusted SOQL query:\n{}",
+            redactor().redact_soql(&updated_soql)
+        );
+
+        let response = self
+            .send(
+                SalesforceOperation::Query,
+                None,
+                self.http
+                    .get(&url)
+                    .bearer_auth(access_token)
+                    .query(&[("q", &updated_soql)]),
+            )
+            .await?;
+        let mut objects = response.json::<Value>().await?;
+        self.field_policies.apply_to_record(None, &mut objects);
+
+        Ok(objects)
     }
 
     pub async fn update_object(
@@ -170,48 +784,599 @@ impl SalesforceService {
         object: String,
         id: String,
         databag: Value,
+    ) -> ServiceResult<()> {
+        self.update_object_with_options(object, id, databag, UpdateOptions::default())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Updates a record like [`Self::update_object`], first fetching the current values of the
+    /// fields being written so the changes the update made can be reported.
+    pub async fn update_object_with_changes(
+        &self,
+        object: String,
+        id: String,
+        databag: Value,
+    ) -> ServiceResult<Vec<FieldChange>> {
+        let options = UpdateOptions {
+            capture_changes: true,
+            ..Default::default()
+        };
+        let changes = self
+            .update_object_with_options(object, id, databag, options)
+            .await?;
+
+        Ok(changes.unwrap_or_default())
+    }
+
+    /// Updates a record, returning the changes it made when they're captured.
+    pub async fn update_object_with_options(
+        &self,
+        object: String,
+        id: String,
+        databag: Value,
+        options: UpdateOptions,
+    ) -> ServiceResult<Option<Vec<FieldChange>>> {
+        self.validate_update(&object, &databag).await?;
+
+        if !options.capture_changes {
+            self.send_update(&object, &id, &databag, &options.preconditions)
+                .await?;
+            return Ok(None);
+        }
+
+        let fields: Vec<String> = databag
+            .as_object()
+            .into_iter()
+            .flat_map(|fields| fields.keys())
+            .filter(|field| *field != "attributes")
+            .cloned()
+            .collect();
+        let before = self.get_record(&object, &id, Some(&fields)).await?.record;
+
+        self.send_update(&object, &id, &databag, &options.preconditions)
+            .await?;
+
+        let mut after = databag;
+        self.field_policies
+            .apply_to_record(Some(&object), &mut after);
+
+        Ok(Some(diff_record(&before, &after)))
+    }
+    /// Rejects update payloads writing forbidden fields or failing validation against describe
+    /// metadata, before anything is sent to Salesforce.
+    async fn validate_update(&self, object: &str, databag: &Value) -> ServiceResult<()> {
+        let forbidden = self.field_policies.forbidden_writes(object, databag);
+        if !forbidden.is_empty() {
+            error!("{object} update payload contains forbidden fields, no update was performed");
+            return Err(ServiceError::Forbidden(format!(
+                "Writing {} is not permitted.",
+                forbidden.join(", ")
+            )));
+        }
+
+        let describe = self.describe_object(object.to_string()).await?;
+
+        // Lookups can also be written through their relationship name, which policies don't list
+        let forbidden: Vec<&str> = databag
+            .as_object()
+            .into_iter()
+            .flat_map(|fields| fields.keys())
+            .filter(|name| describe.field(name).is_none())
+            .filter(|name| {
+                describe
+                    .lookup_field(name)
+                    .is_some_and(|lookup| self.field_policies.forbids_write(object, &lookup.name))
+            })
+            .map(String::as_str)
+            .collect();
+        if !forbidden.is_empty() {
+            error!("{object} update payload contains forbidden fields, no update was performed");
+            return Err(ServiceError::Forbidden(format!(
+                "Writing {} is not permitted.",
+                forbidden.join(", ")
+            )));
+        }
+
+        if let Err(violations) = validate_databag(&describe, databag, WriteOperation::Update) {
+            error!("{object} update payload failed validation, no update was performed");
+            return Err(ServiceError::PayloadInvalid(object.to_string(), violations));
+        }
+
+        Ok(())
+    }
+
+    async fn send_update(
+        &self,
+        object: &str,
+        id: &str,
+        databag: &Value,
+        preconditions: &WritePreconditions,
     ) -> ServiceResult<()> {
         let access_token = self.get_access_token().await?;
+        let url = self.versioned_url(&format!("sobjects/{object}/{id}"))?;
 
-        match self.instance_url.try_lock() {
-            Ok(lock) => match lock.as_ref() {
-                None => Err(ServiceError::InstanceUrlNotFound),
-                Some(instance_url) => {
-                    info!("Updating {object} object {id}");
-
-                    let url = format!("{instance_url}/services/data/v59.0/sobjects/{object}/{id}");
-                    let response = self
-                        .http
-                        .patch(&url)
-                        .bearer_auth(access_token)
-                        .json(&databag)
-                        .send()
-                        .await?;
-
-                    if response.status() == StatusCode::NOT_FOUND {
-                        error!("{object} object {id} was not found, not update was performed");
-                        return Err(ServiceError::ObjectNotFound);
-                    }
+        info!("Updating {object} object {id}");
+        debug!(
+            "{object} object {id} update payload: {}",
+            redactor().redact_value(databag)
+        );
+
+        let request = self
+            .http
+            .patch(&url)
+            .bearer_auth(access_token)
+            .json(databag);
+        let result = self
+            .send(
+                SalesforceOperation::UpdateRecord,
+                Some(object),
+                preconditions.apply(request),
+            )
+            .await;
+
+        match &result {
+            Err(ServiceError::ObjectNotFound) => {
+                error!("{object} object {id} was not found, not update was performed")
+            }
+            Err(ServiceError::PreconditionFailed) => {
+                warn!(
+                    "{object} object {id} was modified since it was read, no update was performed"
+                )
+            }
+            _ => {}
+        }
+
+        // Whether or not the update went through, the record may no longer match what was cached
+        self.record_cache.invalidate(object, id);
+
+        result?;
+
+        Ok(())
+    }
+
+    /// Retrieves the organization's limits from the `/limits` REST resource, refreshing the tracked
+    /// API usage along the way.
+    pub async fn get_limits(&self) -> ServiceResult<Value> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url("limits")?;
+        let response = self
+            .send(
+                SalesforceOperation::Limits,
+                None,
+                self.http.get(&url).bearer_auth(access_token),
+            )
+            .await?;
+        let limits = response.json::<Value>().await?;
+
+        if let Some(usage) = ApiUsage::from_limits_resource(&limits) {
+            self.record_api_usage(usage);
+        }
+
+        Ok(limits)
+    }
+
+    /// Submits a Bulk API 2.0 query job for the given SOQL, returning the job as Salesforce created it.
+    pub async fn create_bulk_query_job(&self, soql: String) -> ServiceResult<BulkQueryJob> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url("jobs/query")?;
+
+        info!("Submitting bulk query job");
+
+        let response = self
+            .send(
+                SalesforceOperation::CreateBulkQueryJob,
+                None,
+                self.http
+                    .post(&url)
+                    .bearer_auth(access_token)
+                    .json(&CreateBulkQueryJobRequest {
+                        operation: "query",
+                        query: &soql,
+                    }),
+            )
+            .await?;
+        let job = response.json::<BulkQueryJob>().await?;
+
+        info!("Bulk query job {} created", job.id);
+
+        Ok(job)
+    }
+
+    pub async fn get_bulk_query_job(&self, job_id: String) -> ServiceResult<BulkQueryJob> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url(&format!("jobs/query/{job_id}"))?;
+        let response = self
+            .send(
+                SalesforceOperation::GetBulkQueryJob,
+                None,
+                self.http.get(&url).bearer_auth(access_token),
+            )
+            .await?;
+        let job = response.json::<BulkQueryJob>().await?;
+
+        Ok(job)
+    }
+
+    /// Polls the bulk query job until it reaches `JobComplete`, failing if Salesforce aborts or fails
+    /// the job, or if it does not complete within `max_wait`.
+    pub async fn wait_for_bulk_query_job(
+        &self,
+        job_id: String,
+        poll_interval: Duration,
+        max_wait: Duration,
+    ) -> ServiceResult<BulkQueryJob> {
+        let deadline = Instant::now() + max_wait;
+
+        loop {
+            let job = self.get_bulk_query_job(job_id.clone()).await?;
+
+            if job.state.is_terminal() {
+                return match job.state {
+                    BulkJobState::JobComplete => Ok(job),
+                    _ => Err(ServiceError::BulkJobFailed(
+                        job.error_message
+                            .unwrap_or_else(|| format!("Bulk job {job_id} was {:?}.", job.state)),
+                    )),
+                };
+            }
+
+            if Instant::now() + poll_interval > deadline {
+                return Err(ServiceError::BulkJobTimedOut(job_id));
+            }
+
+            info!("Bulk query job {job_id} is {:?}, polling again", job.state);
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Retrieves a single chunk of CSV results, starting at `locator` when provided.
+    pub async fn get_bulk_query_results(
+        &self,
+        job_id: String,
+        locator: Option<String>,
+        max_records: Option<u32>,
+    ) -> ServiceResult<BulkQueryResultChunk> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url(&format!("jobs/query/{job_id}/results"))?;
 
-                    if response.status() != StatusCode::NO_CONTENT {
-                        let error_response = response.json::<Value>().await?;
-                        return Err(ServiceError::ObjectUpdateFailed(error_response));
+        let mut query = Vec::new();
+        if let Some(locator) = locator {
+            query.push(("locator", locator));
+        }
+        if let Some(max_records) = max_records {
+            query.push(("maxRecords", max_records.to_string()));
+        }
+
+        let response = self
+            .send(
+                SalesforceOperation::GetBulkQueryResults,
+                None,
+                self.http
+                    .get(&url)
+                    .bearer_auth(access_token)
+                    .header(ACCEPT, "text/csv")
+                    .query(&query),
+            )
+            .await?;
+        let headers = response.headers();
+        let locator = BulkQueryResultChunk::parse_locator(
+            headers
+                .get(SFORCE_LOCATOR_HEADER)
+                .and_then(|value| value.to_str().ok()),
+        );
+        let number_of_records = headers
+            .get(SFORCE_NUMBER_OF_RECORDS_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let csv = response.text().await?;
+
+        Ok(BulkQueryResultChunk {
+            csv,
+            locator,
+            number_of_records,
+        })
+    }
+
+    /// Streams every CSV chunk of a completed bulk query job, following `Sforce-Locator` until
+    /// Salesforce reports there are no more results.
+    pub fn stream_bulk_query_results(
+        &self,
+        job_id: String,
+        max_records: Option<u32>,
+    ) -> impl Stream<Item = ServiceResult<BulkQueryResultChunk>> + '_ {
+        stream::try_unfold(Some(None), move |next_locator: Option<Option<String>>| {
+            let job_id = job_id.clone();
+            async move {
+                match next_locator {
+                    None => Ok(None),
+                    Some(locator) => {
+                        let chunk = self
+                            .get_bulk_query_results(job_id, locator, max_records)
+                            .await?;
+                        let next = chunk.locator.clone().map(Some);
+                        Ok(Some((chunk, next)))
                     }
+                }
+            }
+        })
+    }
+
+    /// Applies the field policies to a chunk of bulk query results on the object, which unlike
+    /// query results carry no record types. Columns the caller can't access are dropped along with
+    /// those of hidden fields, and the values of masked fields are replaced.
+    pub async fn restrict_bulk_query_results(
+        &self,
+        object: &str,
+        chunk: &mut BulkQueryResultChunk,
+        accessible: impl Fn(&str) -> bool,
+    ) -> ServiceResult<()> {
+        let mut access = HashMap::new();
+        for column in csv_columns(&chunk.csv)? {
+            let column_access = if !accessible(&column) {
+                ReadAccess::Hidden
+            } else if self.field_policies.is_empty() {
+                ReadAccess::Visible
+            } else {
+                self.field_read_access(object, &column).await?
+            };
+            access.insert(column, column_access);
+        }
+
+        if access.values().any(|access| *access != ReadAccess::Visible) {
+            chunk.csv = restrict_csv_columns(&chunk.csv, |column| {
+                access.get(column).copied().unwrap_or(ReadAccess::Hidden)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Rejects queries referencing fields hidden by the field policies anywhere in the query.
+    /// Masked fields may be selected, but not filtered, sorted or grouped by or passed to functions
+    /// like `MAX`, as the records matched or the function's result would reveal their values.
+    pub async fn check_query_field_policies(&self, query: &SoqlQuery) -> ServiceResult<()> {
+        if self.field_policies.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending = vec![(query.object.clone(), query)];
+
+        while let Some((object, query)) = pending.pop() {
+            let revealed: HashSet<&str> = query
+                .filter_fields
+                .iter()
+                .chain(&query.function_fields)
+                .map(String::as_str)
+                .collect();
 
-                    Ok(())
+            let mut forbidden = Vec::new();
+            for field in query.referenced_fields() {
+                let access = self.field_read_access(&object, field).await?;
+                if access == ReadAccess::Hidden
+                    || (access == ReadAccess::Masked && revealed.contains(field))
+                {
+                    forbidden.push(field);
                 }
-            },
-            Err(e) => Err(ServiceError::ObjectRetrievalFailed(e.to_string())),
+            }
+
+            if !forbidden.is_empty() {
+                return Err(ServiceError::Forbidden(format!(
+                    "Querying {} on {} is not permitted.",
+                    forbidden.join(", "),
+                    query.object
+                )));
+            }
+
+            // Subqueries in the field list name a child relationship rather than the object
+            if !query.subqueries.is_empty() {
+                let describe = self.describe_object(object.clone()).await?;
+                for subquery in &query.subqueries {
+                    let child = match describe.relationship(&subquery.object) {
+                        Some(Relationship::Child { object, .. }) => object,
+                        _ => ALL_OBJECTS.to_string(),
+                    };
+                    pending.push((child, subquery));
+                }
+            }
+
+            pending.extend(
+                query
+                    .semi_joins
+                    .iter()
+                    .map(|semi_join| (semi_join.object.clone(), semi_join)),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The access the field policies grant to a field of the object, following relationship
+    /// traversals like `Account.Owner.Email` to the objects they lead to. Only the policies for
+    /// every object apply past a relationship describe metadata doesn't know.
+    async fn field_read_access(&self, object: &str, path: &str) -> ServiceResult<ReadAccess> {
+        let mut relationships: Vec<&str> = path.split('.').collect();
+        let field = relationships.pop().unwrap_or(path);
+
+        let mut objects = vec![object.to_string()];
+        for relationship in relationships {
+            let mut targets = Vec::new();
+            for object in objects {
+                if object == ALL_OBJECTS {
+                    targets.push(object);
+                    continue;
+                }
+
+                let describe = self.describe_object(object).await?;
+                match describe.lookup_field(relationship) {
+                    Some(lookup) => targets.extend(lookup.reference_to.iter().cloned()),
+                    None => targets.push(ALL_OBJECTS.to_string()),
+                }
+            }
+            objects = targets;
+        }
+
+        Ok(objects
+            .iter()
+            .map(|object| self.field_policies.read_access(object, field))
+            .max()
+            .unwrap_or(ReadAccess::Visible))
+    }
+
+    /// Describes an sObject, serving cached metadata while it's fresh and revalidating it with
+    /// `If-Modified-Since` once it's gone stale.
+    pub async fn describe_object(&self, object: String) -> ServiceResult<Arc<SObjectDescribe>> {
+        // Object names are case-insensitive, so they shouldn't produce separate cache entries
+        let cache_key = object.to_lowercase();
+        let last_modified = {
+            let cache = self.describe_cache.lock().await;
+            match cache.get(&cache_key) {
+                Some(cached) if cached.validated_at.elapsed() < self.describe_cache_duration => {
+                    return Ok(cached.value.clone());
+                }
+                Some(cached) => cached.last_modified.clone(),
+                None => None,
+            }
+        };
+
+        let path = format!("sobjects/{object}/describe");
+        let revalidated = self
+            .fetch_describe::<SObjectDescribe>(
+                SalesforceOperation::DescribeObject,
+                Some(&object),
+                &path,
+                last_modified,
+            )
+            .await?;
+
+        let mut cache = self.describe_cache.lock().await;
+        let describe = match (revalidated, cache.get_mut(&cache_key)) {
+            (None, Some(cached)) => {
+                info!("Describe metadata for {object} has not changed");
+                cached.validated_at = Instant::now();
+                cached.value.clone()
+            }
+            (Some((describe, last_modified)), _) => {
+                let cached = CachedDescribe::new(describe, last_modified);
+                let describe = cached.value.clone();
+                cache.insert(cache_key, cached);
+                describe
+            }
+            (None, None) => {
+                return Err(ServiceError::ObjectRetrievalFailed(
+                    "Describe metadata was not modified but no cached copy exists.".to_string(),
+                ))
+            }
+        };
+
+        Ok(describe)
+    }
+
+    /// Lists every sObject available to the organization, cached in the same manner as
+    /// [`SalesforceService::describe_object`].
+    pub async fn describe_global(&self) -> ServiceResult<Arc<GlobalDescribe>> {
+        let last_modified = {
+            let cache = self.global_describe_cache.lock().await;
+            match cache.as_ref() {
+                Some(cached) if cached.validated_at.elapsed() < self.describe_cache_duration => {
+                    return Ok(cached.value.clone());
+                }
+                Some(cached) => cached.last_modified.clone(),
+                None => None,
+            }
+        };
+
+        let revalidated = self
+            .fetch_describe::<GlobalDescribe>(
+                SalesforceOperation::DescribeGlobal,
+                None,
+                "sobjects",
+                last_modified,
+            )
+            .await?;
+
+        let mut cache = self.global_describe_cache.lock().await;
+        let describe = match (revalidated, cache.as_mut()) {
+            (None, Some(cached)) => {
+                cached.validated_at = Instant::now();
+                cached.value.clone()
+            }
+            (Some((describe, last_modified)), _) => {
+                let cached = CachedDescribe::new(describe, last_modified);
+                let describe = cached.value.clone();
+                *cache = Some(cached);
+                describe
+            }
+            (None, None) => {
+                return Err(ServiceError::ObjectRetrievalFailed(
+                    "Describe metadata was not modified but no cached copy exists.".to_string(),
+                ))
+            }
+        };
+
+        Ok(describe)
+    }
+
+    /// Requests describe metadata, returning `None` when Salesforce reports it has not been modified
+    /// since `last_modified`.
+    async fn fetch_describe<T: DeserializeOwned>(
+        &self,
+        operation: SalesforceOperation,
+        object: Option<&str>,
+        path: &str,
+        last_modified: Option<String>,
+    ) -> ServiceResult<Option<(T, Option<String>)>> {
+        let access_token = self.get_access_token().await?;
+        let url = self.versioned_url(path)?;
+
+        let mut request = self.http.get(&url).bearer_auth(access_token);
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
         }
+
+        let response = self.send(operation, object, request).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        // Describe responses don't always carry a Last-Modified header, in which case the time
+        // Salesforce served the response is the next best revalidation point
+        let last_modified = response
+            .headers()
+            .get(LAST_MODIFIED)
+            .or_else(|| response.headers().get(DATE))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let describe = response.json::<T>().await?;
+
+        Ok(Some((describe, last_modified)))
     }
 }
 
+/// The status Salesforce responded with, or `error` when the request never received a response.
+fn response_status(result: &ServiceResult<Response>) -> String {
+    match result {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(ServiceError::SalesforceRequestFailed(status, _)) => status.to_string(),
+        Err(ServiceError::ObjectNotFound) => StatusCode::NOT_FOUND.as_u16().to_string(),
+        Err(_) => "error".to_string(),
+    }
+}
+
+/// Whether Salesforce rejected the access token a request was sent with, such as once it expires
+/// ahead of the cached expiry or the session is revoked.
+fn is_invalid_session(error: &ServiceError) -> bool {
+    matches!(
+        error,
+        ServiceError::SalesforceRequestFailed(_, errors)
+            if errors.iter().any(|error| error.error_code == "INVALID_SESSION_ID")
+    )
+}
+
 #[derive(Debug, Deserialize)]
 struct AccessTokenResponse {
     pub access_token: String,
     pub instance_url: String,
-    pub id: String,
-    pub token_type: String,
-    pub issued_at: String,
-    pub signature: String,
 }
@@ -0,0 +1,851 @@
+//! A SOQL parser for checking queries before they're sent to Salesforce. It understands enough of
+//! the grammar to find the objects and fields a query touches, its `LIMIT` and any `FOR UPDATE`,
+//! reporting malformed queries with the position of the problem instead of waiting on Salesforce
+//! to answer with `MALFORMED_QUERY`.
+
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ServiceError, ServiceResult};
+
+/// Keywords that end a select item, object name or expression, so they can't be mistaken for aliases.
+const RESERVED_WORDS: [&str; 26] = [
+    "SELECT", "FROM", "WHERE", "WITH", "GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET", "FOR",
+    "UPDATE", "USING", "AND", "OR", "NOT", "ASC", "DESC", "NULLS", "IN", "LIKE", "INCLUDES",
+    "EXCLUDES", "TYPEOF", "WHEN", "THEN", "END",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SoqlSyntaxError {
+    pub message: String,
+    /// One based character offset into the query where the problem was found.
+    pub position: usize,
+}
+
+impl Display for SoqlSyntaxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}.", self.message, self.position)
+    }
+}
+
+/// The parts of a query that guardrails and authorization are concerned with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SoqlQuery {
+    /// The object queried, or the relationship name for subqueries in a field list.
+    pub object: String,
+    /// Fields selected, including those passed to functions.
+    pub fields: Vec<String>,
+    /// Fields the query filters, groups or sorts by.
+    pub filter_fields: Vec<String>,
+    /// Selected fields passed to functions such as `MAX(Amount)`, whose results are returned
+    /// under an alias or `expr0` rather than the field's name. Also included in `fields`.
+    pub function_fields: Vec<String>,
+    /// Relationship subqueries in the field list.
+    pub subqueries: Vec<SoqlQuery>,
+    /// The fields `TYPEOF` expressions select from each object a polymorphic relationship references.
+    pub typeof_branches: Vec<TypeOfBranch>,
+    /// Subqueries filtering on another object's records, e.g. `Id IN (SELECT AccountId FROM Contact)`.
+    pub semi_joins: Vec<SoqlQuery>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+    pub for_update: bool,
+    /// Whether the query only counts records with `COUNT()`, returning no rows.
+    pub count_only: bool,
+}
+
+/// The fields a `TYPEOF` selects when its relationship references a record of `object`, or of any
+/// object the other branches don't name for its `ELSE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeOfBranch {
+    pub relationship: String,
+    pub object: Option<String>,
+    pub fields: Vec<String>,
+}
+
+impl SoqlQuery {
+    /// Every field the query selects or filters on.
+    pub fn referenced_fields(&self) -> impl Iterator<Item = &str> {
+        self.fields
+            .iter()
+            .chain(&self.filter_fields)
+            .map(String::as_str)
+    }
+}
+
+/// Parses a SOQL query.
+pub fn parse(soql: &str) -> Result<SoqlQuery, SoqlSyntaxError> {
+    let tokens = tokenize(soql)?;
+    let mut parser = Parser { tokens, index: 0 };
+
+    let query = parser.query()?;
+
+    match parser.peek() {
+        Token {
+            kind: TokenKind::End,
+            ..
+        } => Ok(query),
+        token => Err(token.unexpected()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct QueryGuardrailConfiguration {
+    /// Rejects queries without a `LIMIT`, other than those only counting records.
+    pub require_limit: bool,
+    /// Largest `LIMIT` a query may have.
+    pub max_limit: u64,
+}
+
+impl Default for QueryGuardrailConfiguration {
+    fn default() -> Self {
+        Self {
+            require_limit: true,
+            max_limit: 2000,
+        }
+    }
+}
+
+impl QueryGuardrailConfiguration {
+    /// Ensures the query can't lock records or return an unbounded number of them.
+    pub fn check(&self, query: &SoqlQuery) -> ServiceResult<()> {
+        if query.for_update {
+            return Err(ServiceError::QueryRejected(
+                "Queries may not lock records with FOR UPDATE.".to_string(),
+            ));
+        }
+
+        match query.limit {
+            Some(limit) if limit > self.max_limit => Err(ServiceError::QueryRejected(format!(
+                "LIMIT {limit} exceeds the maximum of {}.",
+                self.max_limit
+            ))),
+            None if self.require_limit && !query.count_only => {
+                Err(ServiceError::QueryRejected(format!(
+                    "Queries must include a LIMIT of at most {}.",
+                    self.max_limit
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    /// Keywords, names and unquoted literals such as `true` or `LAST_N_DAYS:30`.
+    Word(String),
+    /// Quoted strings, numbers, dates and date times.
+    Literal(String),
+    Operator(String),
+    OpenParen,
+    CloseParen,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_reserved(&self) -> bool {
+        RESERVED_WORDS
+            .iter()
+            .any(|keyword| self.is_keyword(keyword))
+    }
+
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Word(text) | TokenKind::Literal(text) | TokenKind::Operator(text) => {
+                format!("'{text}'")
+            }
+            TokenKind::OpenParen => "'('".to_string(),
+            TokenKind::CloseParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::End => "end of query".to_string(),
+        }
+    }
+
+    fn unexpected(&self) -> SoqlSyntaxError {
+        self.error(format!("Unexpected {}", self.describe()))
+    }
+
+    fn error(&self, message: String) -> SoqlSyntaxError {
+        SoqlSyntaxError {
+            message,
+            position: self.position,
+        }
+    }
+}
+
+fn tokenize(soql: &str) -> Result<Vec<Token>, SoqlSyntaxError> {
+    let chars: Vec<char> = soql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let start = index;
+        let position = index + 1;
+
+        let kind = match c {
+            _ if c.is_whitespace() => {
+                index += 1;
+                continue;
+            }
+            '(' => {
+                index += 1;
+                TokenKind::OpenParen
+            }
+            ')' => {
+                index += 1;
+                TokenKind::CloseParen
+            }
+            ',' => {
+                index += 1;
+                TokenKind::Comma
+            }
+            '\'' => {
+                index += 1;
+                loop {
+                    match chars.get(index) {
+                        None => {
+                            return Err(SoqlSyntaxError {
+                                message: "Unterminated string literal".to_string(),
+                                position,
+                            })
+                        }
+                        Some('\\') => index += 2,
+                        Some('\'') => break,
+                        Some(_) => index += 1,
+                    }
+                }
+                index += 1;
+                TokenKind::Literal(chars[start..index].iter().collect())
+            }
+            '=' | '!' | '<' | '>' => {
+                let operator: String = chars[index..chars.len().min(index + 2)].iter().collect();
+                let operator = match operator.as_str() {
+                    "!=" | "<>" | "<=" | ">=" => operator,
+                    _ if c == '!' => {
+                        return Err(SoqlSyntaxError {
+                            message: "Unexpected character '!'".to_string(),
+                            position,
+                        })
+                    }
+                    _ => c.to_string(),
+                };
+                index += operator.len();
+                TokenKind::Operator(operator)
+            }
+            _ if c.is_ascii_digit()
+                || (matches!(c, '-' | '+')
+                    && chars.get(index + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                index += 1;
+                while chars.get(index).is_some_and(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '-' | '+')
+                }) {
+                    index += 1;
+                }
+                TokenKind::Literal(chars[start..index].iter().collect())
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                while chars
+                    .get(index)
+                    .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.'))
+                {
+                    index += 1;
+                }
+                // Date literals taking a parameter, e.g. `LAST_N_DAYS:30`
+                if chars.get(index) == Some(&':') {
+                    index += 1;
+                    while chars.get(index).is_some_and(char::is_ascii_digit) {
+                        index += 1;
+                    }
+                }
+                TokenKind::Word(chars[start..index].iter().collect())
+            }
+            _ => {
+                return Err(SoqlSyntaxError {
+                    message: format!("Unexpected character '{c}'"),
+                    position,
+                })
+            }
+        };
+
+        tokens.push(Token { kind, position });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: chars.len() + 1,
+    });
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_keyword(keyword)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let accepted = self.peek_keyword(keyword);
+        if accepted {
+            self.index += 1;
+        }
+        accepted
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SoqlSyntaxError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            let token = self.peek();
+            Err(token.error(format!("Expected {keyword} but found {}", token.describe())))
+        }
+    }
+
+    fn accept(&mut self, kind: &TokenKind) -> bool {
+        let accepted = &self.peek().kind == kind;
+        if accepted {
+            self.index += 1;
+        }
+        accepted
+    }
+
+    fn expect(&mut self, kind: &TokenKind, description: &str) -> Result<(), SoqlSyntaxError> {
+        if self.accept(kind) {
+            Ok(())
+        } else {
+            let token = self.peek();
+            Err(token.error(format!(
+                "Expected {description} but found {}",
+                token.describe()
+            )))
+        }
+    }
+
+    /// A field, object or relationship name.
+    fn name(&mut self, description: &str) -> Result<String, SoqlSyntaxError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Word(name) if !token.is_reserved() => Ok(name.clone()),
+            _ => Err(token.error(format!(
+                "Expected {description} but found {}",
+                token.describe()
+            ))),
+        }
+    }
+
+    fn integer(&mut self, clause: &str) -> Result<u64, SoqlSyntaxError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Literal(value) => value
+                .parse()
+                .map_err(|_| token.error(format!("{clause} must be a whole number"))),
+            _ => Err(token.error(format!("{clause} must be a whole number"))),
+        }
+    }
+
+    fn skip_alias(&mut self) {
+        if matches!(self.peek().kind, TokenKind::Word(_)) && !self.peek().is_reserved() {
+            self.index += 1;
+        }
+    }
+
+    fn query(&mut self) -> Result<SoqlQuery, SoqlSyntaxError> {
+        let mut query = SoqlQuery::default();
+
+        self.expect_keyword("SELECT")?;
+        self.select_list(&mut query)?;
+        self.expect_keyword("FROM")?;
+        query.object = self.name("an object name")?;
+
+        let alias = match &self.peek().kind {
+            TokenKind::Word(alias) if !self.peek().is_reserved() => {
+                let alias = alias.clone();
+                self.index += 1;
+                Some(alias)
+            }
+            _ => None,
+        };
+
+        if self.accept_keyword("USING") {
+            self.expect_keyword("SCOPE")?;
+            self.name("a scope")?;
+        }
+
+        if self.accept_keyword("WHERE") {
+            self.condition(&mut query)?;
+        }
+
+        if self.accept_keyword("WITH") {
+            if self.accept_keyword("DATA") {
+                self.expect_keyword("CATEGORY")?;
+                self.data_category_filter()?;
+            } else {
+                self.name("SECURITY_ENFORCED, USER_MODE, SYSTEM_MODE or DATA CATEGORY")?;
+            }
+        }
+
+        if self.accept_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            if self.accept_keyword("ROLLUP") || self.accept_keyword("CUBE") {
+                self.expect(&TokenKind::OpenParen, "'('")?;
+                self.expression_list(&mut query.filter_fields)?;
+                self.expect(&TokenKind::CloseParen, "')'")?;
+            } else {
+                self.expression_list(&mut query.filter_fields)?;
+            }
+        }
+
+        if self.accept_keyword("HAVING") {
+            self.condition(&mut query)?;
+        }
+
+        if self.accept_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                self.expression(&mut query.filter_fields)?;
+                let _ = self.accept_keyword("ASC") || self.accept_keyword("DESC");
+                if self.accept_keyword("NULLS") && !self.accept_keyword("FIRST") {
+                    self.expect_keyword("LAST")?;
+                }
+                if !self.accept(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        if self.accept_keyword("LIMIT") {
+            query.limit = Some(self.integer("LIMIT")?);
+        }
+
+        if self.accept_keyword("OFFSET") {
+            query.offset = Some(self.integer("OFFSET")?);
+        }
+
+        if self.accept_keyword("FOR") {
+            loop {
+                if self.accept_keyword("UPDATE") {
+                    query.for_update = true;
+                } else if !self.accept_keyword("VIEW") {
+                    self.expect_keyword("REFERENCE")?;
+                }
+                if !self.accept(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        if self.accept_keyword("UPDATE") && !self.accept_keyword("TRACKING") {
+            self.expect_keyword("VIEWSTAT")?;
+        }
+
+        if let Some(alias) = alias {
+            let prefix = format!("{}.", alias.to_lowercase());
+            for field in query
+                .fields
+                .iter_mut()
+                .chain(&mut query.filter_fields)
+                .chain(&mut query.function_fields)
+                .chain(
+                    query
+                        .typeof_branches
+                        .iter_mut()
+                        .map(|branch| &mut branch.relationship),
+                )
+            {
+                if field.to_lowercase().starts_with(&prefix) {
+                    *field = field[prefix.len()..].to_string();
+                }
+            }
+        }
+
+        Ok(query)
+    }
+
+    fn select_list(&mut self, query: &mut SoqlQuery) -> Result<(), SoqlSyntaxError> {
+        let mut items = 0;
+        let mut counts = 0;
+
+        loop {
+            items += 1;
+
+            if self.accept(&TokenKind::OpenParen) {
+                query.subqueries.push(self.query()?);
+                self.expect(&TokenKind::CloseParen, "')' to close the subquery")?;
+            } else if self.accept_keyword("TYPEOF") {
+                let relationship = self.name("a polymorphic relationship name")?;
+                self.typeof_clauses(&relationship, &mut query.typeof_branches)?;
+                query.fields.push(relationship);
+            } else {
+                let is_count = self.peek_keyword("COUNT")
+                    && self.tokens.get(self.index + 2).map(|token| &token.kind)
+                        == Some(&TokenKind::CloseParen);
+                if is_count {
+                    counts += 1;
+                }
+                let is_function = self.tokens.get(self.index + 1).map(|token| &token.kind)
+                    == Some(&TokenKind::OpenParen);
+                let mut fields = Vec::new();
+                self.expression(&mut fields)?;
+                if is_function {
+                    query.function_fields.extend(fields.iter().cloned());
+                }
+                query.fields.extend(fields);
+                self.skip_alias();
+            }
+
+            if !self.accept(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        query.count_only = items == 1 && counts == 1;
+
+        Ok(())
+    }
+
+    /// The `WHEN ... THEN ... ELSE ... END` of a `TYPEOF` expression.
+    fn typeof_clauses(
+        &mut self,
+        relationship: &str,
+        branches: &mut Vec<TypeOfBranch>,
+    ) -> Result<(), SoqlSyntaxError> {
+        loop {
+            let object = if self.accept_keyword("WHEN") {
+                let object = self.name("an object name")?;
+                self.expect_keyword("THEN")?;
+                Some(object)
+            } else if self.accept_keyword("ELSE") {
+                None
+            } else {
+                return self.expect_keyword("END");
+            };
+
+            let mut fields = Vec::new();
+            self.expression_list(&mut fields)?;
+            branches.push(TypeOfBranch {
+                relationship: relationship.to_string(),
+                object,
+                fields,
+            });
+        }
+    }
+
+    fn expression_list(&mut self, fields: &mut Vec<String>) -> Result<(), SoqlSyntaxError> {
+        loop {
+            self.expression(fields)?;
+            if !self.accept(&TokenKind::Comma) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// A field, or a function applied to fields and literals such as `COUNT(Id)` or
+    /// `CALENDAR_YEAR(CreatedDate)`.
+    fn expression(&mut self, fields: &mut Vec<String>) -> Result<(), SoqlSyntaxError> {
+        let name = self.name("a field name")?;
+
+        if !self.accept(&TokenKind::OpenParen) {
+            fields.push(name);
+            return Ok(());
+        }
+
+        if self.accept(&TokenKind::CloseParen) {
+            let token = &self.tokens[self.index - 2];
+            if !name.eq_ignore_ascii_case("COUNT") {
+                return Err(token.error(format!("{name} requires an argument")));
+            }
+            return Ok(());
+        }
+
+        loop {
+            match &self.peek().kind {
+                TokenKind::Literal(_) => {
+                    self.index += 1;
+                }
+                _ => self.expression(fields)?,
+            }
+            if !self.accept(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        self.expect(&TokenKind::CloseParen, "')'")
+    }
+
+    fn condition(&mut self, query: &mut SoqlQuery) -> Result<(), SoqlSyntaxError> {
+        loop {
+            self.condition_term(query)?;
+            if !(self.accept_keyword("AND") || self.accept_keyword("OR")) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn condition_term(&mut self, query: &mut SoqlQuery) -> Result<(), SoqlSyntaxError> {
+        if self.accept_keyword("NOT") {
+            return self.condition_term(query);
+        }
+
+        if self.accept(&TokenKind::OpenParen) {
+            self.condition(query)?;
+            return self.expect(&TokenKind::CloseParen, "')'");
+        }
+
+        self.expression(&mut query.filter_fields)?;
+
+        if let TokenKind::Operator(_) = self.peek().kind {
+            self.index += 1;
+            return self.value();
+        }
+
+        if self.accept_keyword("LIKE") {
+            return self.value();
+        }
+
+        let negated = self.accept_keyword("NOT");
+        let set_operator = self.accept_keyword("IN")
+            || (!negated && (self.accept_keyword("INCLUDES") || self.accept_keyword("EXCLUDES")));
+
+        if !set_operator {
+            let token = self.peek();
+            return Err(token.error(format!(
+                "Expected a comparison operator but found {}",
+                token.describe()
+            )));
+        }
+
+        self.expect(&TokenKind::OpenParen, "'('")?;
+        if self.peek_keyword("SELECT") {
+            query.semi_joins.push(self.query()?);
+        } else {
+            loop {
+                self.value()?;
+                if !self.accept(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(&TokenKind::CloseParen, "')'")
+    }
+
+    /// A literal compared against, including unquoted ones like `null`, `TODAY` or `LAST_N_DAYS:30`.
+    fn value(&mut self) -> Result<(), SoqlSyntaxError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Literal(_) => Ok(()),
+            TokenKind::Word(_) if !token.is_reserved() => Ok(()),
+            _ => Err(token.error(format!("Expected a value but found {}", token.describe()))),
+        }
+    }
+
+    /// Data category selectors, which name categories rather than fields.
+    fn data_category_filter(&mut self) -> Result<(), SoqlSyntaxError> {
+        loop {
+            self.name("a data category group")?;
+            let token = self.next();
+            let selector = ["AT", "ABOVE", "BELOW", "ABOVE_OR_BELOW"]
+                .iter()
+                .any(|selector| token.is_keyword(selector));
+            if !selector {
+                return Err(token.error(format!(
+                    "Expected a data category selector but found {}",
+                    token.describe()
+                )));
+            }
+
+            if self.accept(&TokenKind::OpenParen) {
+                loop {
+                    self.name("a data category")?;
+                    if !self.accept(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.expect(&TokenKind::CloseParen, "')'")?;
+            } else {
+                self.name("a data category")?;
+            }
+
+            if !self.accept_keyword("AND") {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subqueries_and_semi_joins_are_parsed_separately() {
+        let query = parse(
+            "SELECT Id, (SELECT LastName FROM Contacts WHERE Email != null) FROM Account \
+             WHERE Id IN (SELECT AccountId FROM Opportunity WHERE StageName = 'Closed Won') LIMIT 5",
+        )
+        .unwrap();
+
+        assert_eq!(query.object, "Account");
+        assert_eq!(query.fields, ["Id"]);
+        assert_eq!(query.filter_fields, ["Id"]);
+        assert_eq!(query.limit, Some(5));
+
+        let [subquery] = query.subqueries.as_slice() else {
+            panic!("expected a single subquery");
+        };
+        assert_eq!(subquery.object, "Contacts");
+        assert_eq!(subquery.fields, ["LastName"]);
+        assert_eq!(subquery.filter_fields, ["Email"]);
+
+        let [semi_join] = query.semi_joins.as_slice() else {
+            panic!("expected a single semi-join");
+        };
+        assert_eq!(semi_join.object, "Opportunity");
+        assert_eq!(semi_join.fields, ["AccountId"]);
+        assert_eq!(semi_join.filter_fields, ["StageName"]);
+    }
+
+    #[test]
+    fn typeof_branches_keep_the_object_their_fields_are_selected_from() {
+        let query = parse(
+            "SELECT Id, TYPEOF What WHEN Account THEN Phone, Owner.Email WHEN Opportunity THEN Amount \
+             ELSE Name END FROM Event LIMIT 10",
+        )
+        .unwrap();
+
+        assert_eq!(query.fields, ["Id", "What"]);
+        assert_eq!(
+            query.typeof_branches,
+            [
+                TypeOfBranch {
+                    relationship: "What".to_string(),
+                    object: Some("Account".to_string()),
+                    fields: vec!["Phone".to_string(), "Owner.Email".to_string()],
+                },
+                TypeOfBranch {
+                    relationship: "What".to_string(),
+                    object: Some("Opportunity".to_string()),
+                    fields: vec!["Amount".to_string()],
+                },
+                TypeOfBranch {
+                    relationship: "What".to_string(),
+                    object: None,
+                    fields: vec!["Name".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn object_aliases_are_stripped_and_field_aliases_skipped() {
+        let query = parse(
+            "SELECT a.Name, MAX(a.AnnualRevenue) revenue, a.Owner.Email FROM Account a \
+             GROUP BY a.Name, a.Owner.Email ORDER BY a.Name",
+        )
+        .unwrap();
+
+        assert_eq!(query.fields, ["Name", "AnnualRevenue", "Owner.Email"]);
+        assert_eq!(query.function_fields, ["AnnualRevenue"]);
+        assert_eq!(query.filter_fields, ["Name", "Owner.Email", "Name"]);
+        assert!(!query.count_only);
+    }
+
+    #[test]
+    fn keywords_in_quoted_literals_are_not_parsed() {
+        let query = parse(
+            "SELECT Id FROM Account WHERE Name = 'O\\'Brien FROM Contact WHERE' \
+             AND Description LIKE '%(SELECT Id FROM Lead) LIMIT 5 FOR UPDATE%' LIMIT 10",
+        )
+        .unwrap();
+
+        assert_eq!(query.object, "Account");
+        assert_eq!(query.filter_fields, ["Name", "Description"]);
+        assert!(query.subqueries.is_empty());
+        assert!(query.semi_joins.is_empty());
+        assert_eq!(query.limit, Some(10));
+        assert!(!query.for_update);
+    }
+
+    #[test]
+    fn syntax_errors_report_their_position() {
+        assert_eq!(
+            parse("SELECT Id FROM Account WHERE Name = 'Acme").unwrap_err(),
+            SoqlSyntaxError {
+                message: "Unterminated string literal".to_string(),
+                position: 37,
+            }
+        );
+        assert_eq!(parse("SELECT FROM Account").unwrap_err().position, 8);
+        assert_eq!(
+            parse("SELECT Id FROM Account LIMIT ten").unwrap_err(),
+            SoqlSyntaxError {
+                message: "LIMIT must be a whole number".to_string(),
+                position: 30,
+            }
+        );
+    }
+
+    #[test]
+    fn guardrails_require_a_bounded_limit() {
+        let guardrails = QueryGuardrailConfiguration::default();
+        let check = |soql: &str| guardrails.check(&parse(soql).unwrap());
+
+        assert!(check("SELECT Id FROM Account LIMIT 2000").is_ok());
+        assert!(check("SELECT COUNT() FROM Account").is_ok());
+        assert!(matches!(
+            check("SELECT Id FROM Account"),
+            Err(ServiceError::QueryRejected(_))
+        ));
+        assert!(matches!(
+            check("SELECT COUNT(Id) FROM Account"),
+            Err(ServiceError::QueryRejected(_))
+        ));
+        assert!(matches!(
+            check("SELECT Id FROM Account LIMIT 2001"),
+            Err(ServiceError::QueryRejected(_))
+        ));
+        assert!(matches!(
+            check("SELECT Id FROM Account LIMIT 1 FOR UPDATE"),
+            Err(ServiceError::QueryRejected(_))
+        ));
+
+        let unlimited = QueryGuardrailConfiguration {
+            require_limit: false,
+            ..guardrails
+        };
+        assert!(unlimited
+            .check(&parse("SELECT Id FROM Account").unwrap())
+            .is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+//! OpenTelemetry trace export and W3C trace context propagation, linking spans for inbound
+//! requests with the spans of the Salesforce calls made on their behalf.
+
+use std::env;
+
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::export::trace::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Config, Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::{info_span, Instrument, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::errors::ServiceResult;
+use crate::organization::SalesforceOrganization;
+use crate::request_context::current_request_id;
+
+/// Standard OpenTelemetry variable naming the collector to export spans to. Spans are still
+/// created and propagated when it's not set, they just aren't exported anywhere.
+const OTLP_ENDPOINT_VARIABLE: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Standard OpenTelemetry variable naming the service spans are reported under.
+const SERVICE_NAME_VARIABLE: &str = "OTEL_SERVICE_NAME";
+
+const DEFAULT_SERVICE_NAME: &str = "salesforce-api";
+
+#[derive(Debug, Clone)]
+pub struct TelemetryConfiguration {
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+impl TelemetryConfiguration {
+    pub fn from_env() -> Self {
+        Self {
+            otlp_endpoint: env::var(OTLP_ENDPOINT_VARIABLE)
+                .ok()
+                .filter(|endpoint| !endpoint.is_empty()),
+            service_name: env::var(SERVICE_NAME_VARIABLE)
+                .unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string()),
+        }
+    }
+}
+
+/// Builds a tracer provider that exports spans to the configured OTLP collector over gRPC.
+pub fn tracer_provider(config: &TelemetryConfiguration) -> ServiceResult<TracerProvider> {
+    let builder = TracerProvider::builder().with_config(trace_config(&config.service_name));
+
+    let provider = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint)
+                .build_span_exporter()?;
+            builder
+                .with_batch_exporter(exporter, runtime::Tokio)
+                .build()
+        }
+        None => builder.build(),
+    };
+
+    Ok(provider)
+}
+
+/// Builds a tracer provider that hands every span to the given exporter as soon as it ends, such
+/// as an in-memory exporter when verifying what the service exports.
+pub fn tracer_provider_with_exporter<E>(exporter: E) -> TracerProvider
+where
+    E: SpanExporter + 'static,
+{
+    TracerProvider::builder()
+        .with_config(trace_config(DEFAULT_SERVICE_NAME))
+        .with_simple_exporter(exporter)
+        .build()
+}
+
+fn trace_config(service_name: &str) -> Config {
+    Config::default().with_resource(Resource::new([KeyValue::new(
+        "service.name",
+        service_name.to_string(),
+    )]))
+}
+
+/// Creates the layer bridging `tracing` spans to the provider, installing the W3C trace context
+/// propagator used to continue traces across service boundaries.
+pub fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME))
+}
+
+/// Flushes any spans waiting to be exported.
+pub fn shutdown(provider: TracerProvider) {
+    for result in provider.force_flush() {
+        if let Err(e) = result {
+            eprintln!("Failed to export remaining spans: {e}");
+        }
+    }
+}
+
+/// Wraps each request in a server span, continuing the caller's trace when a `traceparent` header
+/// is present.
+pub async fn trace_requests(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    // Only recognized organizations are recorded, so callers can't fill the attribute with anything
+    let organization = request
+        .headers()
+        .get("SF-Organization")
+        .and_then(|header| SalesforceOrganization::try_from(header).ok())
+        .map(|organization| organization.as_str());
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {route}", request.method()),
+        otel.kind = "server",
+        http.method = %request.method(),
+        http.route = route,
+        request_id = current_request_id(),
+        organization,
+        http.status_code = tracing::field::Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&AxumHeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("http.status_code", response.status().as_u16());
+
+    response
+}
+
+/// Adds the current span's trace context to an outbound request's headers.
+pub fn inject_context(headers: &mut reqwest::header::HeaderMap) {
+    let context = Span::current().context();
+
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut ReqwestHeaderInjector(headers))
+    });
+}
+
+// axum's header map comes from `http` 1.0 while reqwest's comes from 0.2, so each gets its own adapter
+struct AxumHeaderExtractor<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for AxumHeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+struct ReqwestHeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for ReqwestHeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        let name = reqwest::header::HeaderName::from_bytes(key.as_bytes());
+        let value = reqwest::header::HeaderValue::from_str(&value);
+
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.0.insert(name, value);
+        }
+    }
+}
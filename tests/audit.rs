@@ -0,0 +1,106 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tempfile::{NamedTempFile, TempDir};
+
+use common::{api_key_router, call, grant, policy_file, MockSalesforce};
+
+const RECORD_PATH: &str = "/objects/Account/001000000000001AAA";
+
+/// Lets the underwriting UI update account names, while reporting may only read accounts.
+fn policy() -> NamedTempFile {
+    let mut update = grant(&["Account"], &["Update"]);
+    update["Fields"] = json!(["Name"]);
+
+    policy_file(json!({
+        "underwriting-ui": [update],
+        "reporting": [grant(&["Account"], &["Read"])],
+    }))
+}
+
+fn router(salesforce: &MockSalesforce, policy: &NamedTempFile, audit: &TempDir) -> axum::Router {
+    let config = json!({
+        "Audit": {
+            "Sink": "Sqlite",
+            "Path": audit.path().join("audit.db").to_string_lossy(),
+        },
+    });
+
+    api_key_router(salesforce, policy, config)
+}
+
+/// The caller, outcome and changes of every audited write, oldest first.
+fn audited(audit: &TempDir) -> Vec<(String, String, Value)> {
+    let connection = Connection::open(audit.path().join("audit.db")).unwrap();
+    let mut statement = connection
+        .prepare("SELECT caller, outcome, changes FROM audit_log ORDER BY id")
+        .unwrap();
+
+    statement
+        .query_map((), |row| {
+            let changes: String = row.get(2)?;
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                serde_json::from_str(&changes).unwrap(),
+            ))
+        })
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[tokio::test]
+async fn denied_writes_are_audited() {
+    let salesforce =
+        MockSalesforce::start(Arc::new(|_, _, _, _| StatusCode::NOT_FOUND.into_response())).await;
+    let policy = policy();
+    let audit = tempfile::tempdir().unwrap();
+    let router = router(&salesforce, &policy, &audit);
+
+    let (status, _, _) = call(
+        &router,
+        "PUT",
+        RECORD_PATH,
+        &[
+            ("SF-Organization", "Underwriting"),
+            ("X-API-Key", "reporting-key"),
+            ("Content-Type", "application/json"),
+        ],
+        r#"{ "Name": "Acme" }"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, _) = call(
+        &router,
+        "PUT",
+        RECORD_PATH,
+        &[
+            ("SF-Organization", "Underwriting"),
+            ("X-API-Key", "ui-key"),
+            ("Content-Type", "application/json"),
+        ],
+        r#"{ "Name": "Acme", "SSN__c": "123-45-6789" }"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    assert_eq!(
+        audited(&audit),
+        [
+            ("reporting".to_string(), "denied".to_string(), Value::Null),
+            (
+                "underwriting-ui".to_string(),
+                "denied".to_string(),
+                json!({ "Name": "Acme", "SSN__c": "***" })
+            ),
+        ]
+    );
+    assert!(salesforce.api_requests().is_empty());
+}
@@ -0,0 +1,266 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+
+use common::{
+    account_describe, api_key_router, as_caller, call, grant, json_response, policy_file,
+    MockSalesforce,
+};
+
+const JOB_ID: &str = "750000000000001AAA";
+
+fn summary(name: &str) -> Value {
+    json!({
+        "name": name,
+        "label": name,
+        "keyPrefix": null,
+        "custom": false,
+        "createable": true,
+        "updateable": true,
+        "deletable": true,
+        "queryable": true,
+    })
+}
+
+fn bulk_job() -> Value {
+    json!({
+        "id": JOB_ID,
+        "operation": "query",
+        "object": "Account",
+        "state": "UploadComplete",
+    })
+}
+
+/// Accounts have loans as children and look up to the user who owns them.
+fn describe_with_relationships() -> Value {
+    let mut describe = account_describe();
+    describe["childRelationships"] = json!([{
+        "childSObject": "Loan__c",
+        "field": "Account__c",
+        "relationshipName": "Loans__r",
+        "cascadeDelete": false,
+    }]);
+
+    let mut owner = describe["fields"][0].clone();
+    owner["name"] = json!("OwnerId");
+    owner["type"] = json!("reference");
+    owner["referenceTo"] = json!(["User"]);
+    owner["relationshipName"] = json!("Owner");
+    describe["fields"].as_array_mut().unwrap().push(owner);
+
+    describe
+}
+
+async fn salesforce() -> MockSalesforce {
+    MockSalesforce::start(Arc::new(|method, uri, _, _| {
+        match (method.as_str(), uri.path()) {
+            ("GET", "/services/data/v59.0/sobjects") => json_response(
+                200,
+                json!({
+                    "encoding": "UTF-8",
+                    "maxBatchSize": 200,
+                    "sobjects": [summary("Account"), summary("Contact"), summary("Loan__c")],
+                }),
+            ),
+            ("GET", "/services/data/v59.0/sobjects/Account/describe") => {
+                json_response(200, describe_with_relationships())
+            }
+            ("GET", "/services/data/v59.0/query/") => {
+                json_response(200, json!({ "totalSize": 0, "done": true, "records": [] }))
+            }
+            ("GET", path) if path.starts_with("/services/data/v59.0/sobjects/Account/") => {
+                json_response(200, json!({ "Id": "001000000000001AAA", "Name": "Acme" }))
+            }
+            ("POST", "/services/data/v59.0/jobs/query") => json_response(200, bulk_job()),
+            ("GET", path) if path == format!("/services/data/v59.0/jobs/query/{JOB_ID}") => {
+                json_response(200, bulk_job())
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }))
+    .await
+}
+
+/// Grants every caller reads and queries of Account, plus Loan__c for the underwriting UI.
+fn policy() -> NamedTempFile {
+    let read_and_query = |objects: &[&str]| grant(objects, &["Read", "Query"]);
+    let mut funding = read_and_query(&["Account"]);
+    funding["Organizations"] = json!(["NationalFunding"]);
+
+    policy_file(json!({
+        "underwriting-ui": [read_and_query(&["Account", "Loan__c"])],
+        "reporting": [read_and_query(&["Account"])],
+        "funding": [funding],
+    }))
+}
+
+#[tokio::test]
+async fn encoded_path_parameters_cannot_reach_other_resources() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    for path in [
+        "/objects/Account/..%2F..%2Fquery%3Fq%3DSELECT%20Id%20FROM%20Loan__c",
+        "/objects/Account/..%2F..%2F..%2Fsobjects%2FLoan__c%2F001000000000001",
+        "/objects/Account%2F..%2FLoan__c/001000000000001AAA",
+        "/objects/Account/001000000000001AAA/..%2F..%2Fquery",
+        "/jobs/query/..%2F..%2Fquery%3Fq%3DSELECT%20Id%20FROM%20Loan__c",
+    ] {
+        let (status, _, body) = call(&router, "GET", path, &as_caller("reporting-key"), "").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}: {body}");
+    }
+
+    assert!(salesforce.api_requests().is_empty());
+}
+
+#[tokio::test]
+async fn valid_path_parameters_are_accepted() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let (status, _, body) = call(
+        &router,
+        "GET",
+        "/objects/Account/001000000000001",
+        &as_caller("reporting-key"),
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["Name"], "Acme");
+}
+
+#[tokio::test]
+async fn describe_global_only_lists_objects_the_caller_may_use() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let names = |body: &Value| -> Vec<String> {
+        body["sobjects"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|sobject| sobject["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let (status, _, body) = call(&router, "GET", "/objects", &as_caller("ui-key"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&body), ["Account", "Loan__c"]);
+
+    let (_, _, body) = call(&router, "GET", "/objects", &as_caller("reporting-key"), "").await;
+    assert_eq!(names(&body), ["Account"]);
+}
+
+#[tokio::test]
+async fn limits_only_report_organizations_the_caller_may_use() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let (status, _, body) = call(
+        &router,
+        "GET",
+        "/limits",
+        &[("X-API-Key", "funding-key")],
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let organizations: Vec<&String> = body.as_object().unwrap().keys().collect();
+    assert_eq!(organizations, ["NationalFunding"]);
+}
+
+#[tokio::test]
+async fn bulk_jobs_are_only_visible_to_the_caller_that_created_them() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let (status, _, job) = call(
+        &router,
+        "POST",
+        "/jobs/query",
+        &as_caller("reporting-key"),
+        "SELECT Id, Name FROM Account",
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(job["id"], JOB_ID);
+
+    let job_path = format!("/jobs/query/{JOB_ID}");
+    let (status, _, _) = call(&router, "GET", &job_path, &as_caller("reporting-key"), "").await;
+    assert_eq!(status, StatusCode::OK);
+
+    for path in [job_path.clone(), format!("{job_path}/results")] {
+        let (status, _, _) = call(&router, "GET", &path, &as_caller("ui-key"), "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+    }
+
+    // Jobs created outside the API have no owner to check against
+    let (status, _, _) = call(
+        &router,
+        "GET",
+        "/jobs/query/750000000000002AAA",
+        &as_caller("reporting-key"),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn queries_are_authorized_for_every_object_they_read() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    for soql in [
+        "SELECT Id, (SELECT SSN__c FROM Loans__r) FROM Account LIMIT 10",
+        "SELECT Id, Owner.Email FROM Account LIMIT 10",
+        "SELECT Id FROM Account WHERE Owner.Email LIKE '%@example.com' LIMIT 10",
+        "SELECT Id, TYPEOF Owner WHEN User THEN Email END FROM Account LIMIT 10",
+    ] {
+        for path in ["/objects/query", "/jobs/query"] {
+            let (status, _, _) =
+                call(&router, "POST", path, &as_caller("reporting-key"), soql).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{path} {soql}");
+        }
+    }
+    assert!(salesforce
+        .api_requests()
+        .iter()
+        .all(|request| request.contains("/describe")));
+
+    // The underwriting UI may query loans, but still not users
+    let (status, _, _) = call(
+        &router,
+        "POST",
+        "/objects/query",
+        &as_caller("ui-key"),
+        "SELECT Id, (SELECT Name FROM Loans__r) FROM Account LIMIT 10",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = call(
+        &router,
+        "POST",
+        "/objects/query",
+        &as_caller("ui-key"),
+        "SELECT Id, Owner.Email FROM Account LIMIT 10",
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
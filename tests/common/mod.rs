@@ -0,0 +1,256 @@
+//! A local stand-in for Salesforce and helpers for calling the router, shared by the integration
+//! tests.
+
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Method, Request, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+use tower::ServiceExt;
+
+use salesforce_api::config::{
+    AggregateSystemConfiguration, SalesforceConfiguration, ServiceConfiguration,
+};
+use salesforce_api::router::ServiceRouter;
+use salesforce_api::salesforce::resolver::SalesforceServiceResolver;
+
+pub type Handler = Arc<dyn Fn(&Method, &Uri, &HeaderMap, &str) -> Response + Send + Sync>;
+
+/// A mock Salesforce instance, recording every request it receives as `METHOD uri`.
+pub struct MockSalesforce {
+    pub token_url: String,
+    pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockSalesforce {
+    /// Starts a mock issuing access tokens from `/token` and answering everything else with the
+    /// handler.
+    pub async fn start(handler: Handler) -> Self {
+        Self::start_with_token(
+            Arc::new(|_, _, _, _| StatusCode::NOT_FOUND.into_response()),
+            handler,
+        )
+        .await
+    }
+
+    /// Starts a mock whose token endpoint is answered by `token`, falling back to issuing a token
+    /// when it answers 404.
+    pub async fn start_with_token(token: Handler, handler: Handler) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let instance_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let log = requests.clone();
+        let base_url = instance_url.clone();
+        let app = Router::new().fallback(
+            move |method: Method, uri: Uri, headers: HeaderMap, body: String| {
+                let (token, handler, log) = (token.clone(), handler.clone(), log.clone());
+                let base_url = base_url.clone();
+                async move {
+                    log.lock().unwrap().push(format!("{method} {uri}"));
+                    if uri.path() != "/token" {
+                        return handler(&method, &uri, &headers, &body);
+                    }
+
+                    let response = token(&method, &uri, &headers, &body);
+                    if response.status() != StatusCode::NOT_FOUND {
+                        return response;
+                    }
+                    Json(json!({ "access_token": "token", "instance_url": base_url }))
+                        .into_response()
+                }
+            },
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self {
+            token_url: format!("{instance_url}/token"),
+            requests,
+        }
+    }
+
+    /// The requests received for paths other than the token endpoint.
+    pub fn api_requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| !request.contains(" /token"))
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn salesforce_configuration(url: &str) -> SalesforceConfiguration {
+    serde_json::from_value(json!({
+        "salesForceUrl": url,
+        "userName": "user",
+        "password": "password",
+        "consumerKey": "key",
+        "consumerSecret": "secret",
+    }))
+    .unwrap()
+}
+
+/// A resolver for every organization pointing at the mock, with `service` merged over a minimal
+/// service configuration allowing anonymous callers.
+pub fn resolver(url: &str, service: Value) -> SalesforceServiceResolver {
+    let mut configuration = json!({
+        "EncryptionBaseUri": "https://encryption.example.com",
+        "Authentication": { "AllowAnonymous": true },
+        "Audit": { "Sink": "Disabled" },
+    });
+    for (key, value) in service.as_object().unwrap() {
+        configuration[key] = value.clone();
+    }
+    let service_config: ServiceConfiguration = serde_json::from_value(configuration).unwrap();
+
+    SalesforceServiceResolver::new(AggregateSystemConfiguration {
+        uw_salesforce_config: salesforce_configuration(url),
+        nf_salesforce_config: salesforce_configuration(url),
+        qb_salesforce_config: salesforce_configuration(url),
+        service_config,
+    })
+}
+
+/// Grants the operations on the objects in the underwriting organization, for every field.
+pub fn grant(objects: &[&str], operations: &[&str]) -> Value {
+    json!({
+        "Organizations": ["Underwriting"],
+        "Objects": objects,
+        "Operations": operations,
+    })
+}
+
+/// Writes an authorization policy granting each caller in `callers` its list of grants.
+pub fn policy_file(callers: Value) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(json!({ "Callers": callers }).to_string().as_bytes())
+        .unwrap();
+    file
+}
+
+/// A router enforcing the policy for callers authenticating with an API key, with `service`
+/// merged over the configuration. Each caller's key is named after it, e.g. `ui-key` for the
+/// underwriting UI, and the funding caller may only use the national funding organization.
+pub fn api_key_router(
+    salesforce: &MockSalesforce,
+    policy: &NamedTempFile,
+    service: Value,
+) -> Router {
+    let mut config = json!({
+        "PolicyPath": policy.path().to_string_lossy(),
+        "Authentication": {
+            "ApiKeys": [
+                { "Caller": "underwriting-ui", "Key": "ui-key" },
+                { "Caller": "reporting", "Key": "reporting-key" },
+                { "Caller": "dialer", "Key": "dialer-key" },
+                { "Caller": "funding", "Key": "funding-key", "Organizations": ["NationalFunding"] },
+            ]
+        },
+    });
+    for (key, value) in service.as_object().unwrap() {
+        config[key] = value.clone();
+    }
+
+    ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap()
+}
+
+/// Headers calling the underwriting organization with the API key.
+pub fn as_caller(key: &'static str) -> [(&'static str, &'static str); 2] {
+    [("SF-Organization", "Underwriting"), ("X-API-Key", key)]
+}
+
+pub async fn call(
+    router: &Router,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (StatusCode, HeaderMap, Value) {
+    let (status, headers, body) = call_raw(router, method, path, headers, body).await;
+
+    (
+        status,
+        headers,
+        serde_json::from_str(&body).unwrap_or(Value::Null),
+    )
+}
+
+/// Calls the router like [`call`], returning the body as text for responses that aren't JSON.
+pub async fn call_raw(
+    router: &Router,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> (StatusCode, HeaderMap, String) {
+    let mut request = Request::builder().method(method).uri(path);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+
+    (status, headers, String::from_utf8_lossy(&body).into_owned())
+}
+
+pub fn json_response(status: u16, body: Value) -> Response {
+    (StatusCode::from_u16(status).unwrap(), Json(body)).into_response()
+}
+
+pub fn account_describe() -> Value {
+    let field = |name: &str, field_type: &str, length: u32, writable: bool, nillable: bool| {
+        json!({
+            "name": name,
+            "label": name,
+            "type": field_type,
+            "length": length,
+            "precision": 0,
+            "scale": 0,
+            "nillable": nillable,
+            "createable": writable,
+            "updateable": writable,
+            "defaultedOnCreate": false,
+            "calculated": false,
+            "custom": name.ends_with("__c"),
+            "unique": false,
+            "externalId": false,
+            "referenceTo": [],
+            "relationshipName": null,
+        })
+    };
+
+    json!({
+        "name": "Account",
+        "label": "Account",
+        "keyPrefix": "001",
+        "custom": false,
+        "createable": true,
+        "updateable": true,
+        "deletable": true,
+        "queryable": true,
+        "fields": [
+            field("Id", "id", 18, false, false),
+            field("Name", "string", 80, true, false),
+            field("Phone", "phone", 40, true, true),
+            field("SSN__c", "string", 11, true, true),
+            field("LastModifiedDate", "datetime", 0, false, false),
+        ],
+        "childRelationships": [],
+    })
+}
@@ -0,0 +1,216 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+
+use common::{
+    account_describe, api_key_router, as_caller, call, call_raw, grant, json_response, policy_file,
+    MockSalesforce,
+};
+
+const JOB_ID: &str = "750000000000001AAA";
+
+const RESULTS_CSV: &str = "\"Id\",\"Name\",\"Phone\",\"SSN__c\"\n\
+    \"001000000000001AAA\",\"Acme\",\"555-0100\",\"123-45-6789\"\n";
+
+/// Contacts look up to their account through `AccountId`.
+fn contact_describe() -> Value {
+    let mut describe = account_describe();
+    describe["name"] = json!("Contact");
+    describe["label"] = json!("Contact");
+    describe["keyPrefix"] = json!("003");
+
+    let mut lookup = describe["fields"][0].clone();
+    lookup["name"] = json!("AccountId");
+    lookup["type"] = json!("reference");
+    lookup["referenceTo"] = json!(["Account"]);
+    lookup["relationshipName"] = json!("Account");
+    describe["fields"].as_array_mut().unwrap().push(lookup);
+
+    describe
+}
+
+async fn salesforce() -> MockSalesforce {
+    MockSalesforce::start(Arc::new(|method, uri, _, _| {
+        match (method.as_str(), uri.path()) {
+            ("GET", "/services/data/v59.0/sobjects/Account/describe") => {
+                json_response(200, account_describe())
+            }
+            ("GET", "/services/data/v59.0/sobjects/Contact/describe") => {
+                json_response(200, contact_describe())
+            }
+            ("GET", "/services/data/v59.0/query/") => json_response(
+                200,
+                json!({
+                    "totalSize": 1,
+                    "done": true,
+                    "records": [{
+                        "attributes": { "type": "Account" },
+                        "Id": "001000000000001AAA",
+                        "Phone": "555-0100",
+                    }],
+                }),
+            ),
+            ("POST", "/services/data/v59.0/jobs/query") => json_response(
+                200,
+                json!({
+                    "id": JOB_ID,
+                    "operation": "query",
+                    "object": "Account",
+                    "state": "UploadComplete",
+                }),
+            ),
+            ("GET", path)
+                if path == format!("/services/data/v59.0/jobs/query/{JOB_ID}/results") =>
+            {
+                (StatusCode::OK, [("Sforce-Locator", "null")], RESULTS_CSV).into_response()
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }))
+    .await
+}
+
+/// Lets the reporting caller query every Account field, and the dialer only the ID and phone number.
+fn policy() -> NamedTempFile {
+    let read_and_query = |objects: &[&str]| grant(objects, &["Read", "Query"]);
+    let mut dialer = read_and_query(&["Account"]);
+    dialer["Fields"] = json!(["Id", "Phone"]);
+
+    policy_file(json!({
+        "reporting": [read_and_query(&["Account", "Contact"])],
+        "dialer": [dialer],
+    }))
+}
+
+/// Hides social security numbers and masks phone numbers on every object.
+fn router(salesforce: &MockSalesforce, policy: &NamedTempFile) -> axum::Router {
+    let config = json!({
+        "Organizations": {
+            "Underwriting": {
+                "FieldPolicies": {
+                    "*": { "Hidden": ["SSN__c"], "Masked": ["Phone"] },
+                },
+            },
+        },
+    });
+
+    api_key_router(salesforce, policy, config)
+}
+
+#[tokio::test]
+async fn queries_revealing_restricted_fields_are_rejected() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = router(&salesforce, &policy);
+
+    for soql in [
+        "SELECT Id FROM Account WHERE SSN__c = '123-45-6789' LIMIT 10",
+        "SELECT Id FROM Account ORDER BY SSN__c LIMIT 10",
+        "SELECT MAX(SSN__c) m FROM Account LIMIT 10",
+        "SELECT Account.SSN__c FROM Contact LIMIT 10",
+        "SELECT Id FROM Contact WHERE AccountId IN (SELECT Id FROM Account WHERE SSN__c LIKE '1%') LIMIT 10",
+        // Masked values can be selected, but not matched against or aggregated
+        "SELECT Id FROM Account WHERE Phone = '555-0100' LIMIT 10",
+        "SELECT MIN(Phone) FROM Account LIMIT 10",
+    ] {
+        for path in ["/objects/query", "/jobs/query"] {
+            let (status, _, _) =
+                call(&router, "POST", path, &as_caller("reporting-key"), soql).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{path} {soql}");
+        }
+    }
+
+    assert!(salesforce
+        .api_requests()
+        .iter()
+        .all(|request| request.contains("/describe")));
+}
+
+#[tokio::test]
+async fn masked_fields_can_be_selected() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = router(&salesforce, &policy);
+
+    let (status, _, result) = call(
+        &router,
+        "POST",
+        "/objects/query",
+        &as_caller("reporting-key"),
+        "SELECT Id, Phone FROM Account LIMIT 10",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["records"][0]["Phone"], "***");
+}
+
+#[tokio::test]
+async fn bulk_results_are_restricted_to_accessible_fields() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = router(&salesforce, &policy);
+    let results = format!("/jobs/query/{JOB_ID}/results");
+
+    let (status, _, _) = call(
+        &router,
+        "POST",
+        "/jobs/query",
+        &as_caller("reporting-key"),
+        "SELECT Id, Name, Phone FROM Account",
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (status, _, csv) =
+        call_raw(&router, "GET", &results, &as_caller("reporting-key"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        csv,
+        "\"Id\",\"Name\",\"Phone\"\n\"001000000000001AAA\",\"Acme\",\"***\"\n"
+    );
+
+    let (status, _, records) = call(
+        &router,
+        "GET",
+        &format!("{results}?format=json"),
+        &as_caller("reporting-key"),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        records,
+        json!([{ "Id": "001000000000001AAA", "Name": "Acme", "Phone": "***" }])
+    );
+
+    // Columns outside the caller's granted fields are dropped too
+    let (status, _, _) = call(
+        &router,
+        "POST",
+        "/jobs/query",
+        &as_caller("dialer-key"),
+        "SELECT Id, Phone FROM Account",
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (status, _, records) = call(
+        &router,
+        "GET",
+        &format!("{results}?format=json"),
+        &as_caller("dialer-key"),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        records,
+        json!([{ "Id": "001000000000001AAA", "Phone": "***" }])
+    );
+}
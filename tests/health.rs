@@ -0,0 +1,61 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use salesforce_api::router::ServiceRouter;
+
+use common::{call, json_response, resolver, MockSalesforce};
+
+/// A mock refusing to issue tokens, leaving every organization unready.
+async fn unreachable_salesforce() -> MockSalesforce {
+    MockSalesforce::start_with_token(
+        Arc::new(|_, _, _, _| json_response(503, json!({ "error": "unavailable" }))),
+        Arc::new(|_, _, _, _| json_response(503, json!([]))),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn unready_organizations_are_reported_without_failing_readiness() {
+    let salesforce = unreachable_salesforce().await;
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, json!({}))).unwrap();
+
+    let (status, _, body) = call(&router, "GET", "/health/ready", &[], "").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["allOrganizationsReady"], false);
+    assert_eq!(body["organizations"]["Underwriting"]["ready"], false);
+    assert_eq!(
+        body["organizations"]["Underwriting"]["authenticated"],
+        false
+    );
+}
+
+#[tokio::test]
+async fn readiness_can_require_every_organization() {
+    let salesforce = unreachable_salesforce().await;
+    let config = json!({ "ReadinessRequiresAllOrganizations": true });
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (status, _, body) = call(&router, "GET", "/health/ready", &[], "").await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+}
+
+#[tokio::test]
+async fn reachable_organizations_are_ready() {
+    let salesforce =
+        MockSalesforce::start(Arc::new(|_, _, _, _| json_response(404, json!([])))).await;
+    let config = json!({ "ReadinessRequiresAllOrganizations": true });
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (status, _, body) = call(&router, "GET", "/health/ready", &[], "").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["allOrganizationsReady"], true);
+}
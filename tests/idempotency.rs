@@ -0,0 +1,139 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::json;
+
+use salesforce_api::router::ServiceRouter;
+
+use common::{account_describe, call, json_response, resolver, MockSalesforce};
+
+const RECORD_PATH: &str = "/objects/Account/001000000000001AAA";
+
+/// Answers updates after `delay`, blocking the mock's worker thread in the meantime.
+async fn salesforce(delay: Duration) -> MockSalesforce {
+    MockSalesforce::start(Arc::new(move |method, uri, _, _| {
+        match (method.as_str(), uri.path()) {
+            ("GET", "/services/data/v59.0/sobjects/Account/describe") => {
+                json_response(200, account_describe())
+            }
+            ("PATCH", "/services/data/v59.0/sobjects/Account/001000000000001AAA") => {
+                std::thread::sleep(delay);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }))
+    .await
+}
+
+fn router(salesforce: &MockSalesforce, idempotency: serde_json::Value) -> axum::Router {
+    let config = json!({ "Idempotency": idempotency });
+
+    ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap()
+}
+
+async fn update(router: &axum::Router, key: &str, name: &str) -> (StatusCode, bool) {
+    let (status, headers, _) = call(
+        router,
+        "PUT",
+        RECORD_PATH,
+        &[
+            ("SF-Organization", "Underwriting"),
+            ("Content-Type", "application/json"),
+            ("Idempotency-Key", key),
+        ],
+        &json!({ "Name": name }).to_string(),
+    )
+    .await;
+
+    (status, headers.contains_key("Idempotent-Replayed"))
+}
+
+fn updates_sent(salesforce: &MockSalesforce) -> usize {
+    salesforce
+        .api_requests()
+        .iter()
+        .filter(|request| request.starts_with("PATCH"))
+        .count()
+}
+
+#[tokio::test]
+async fn repeated_writes_are_replayed() {
+    let salesforce = salesforce(Duration::ZERO).await;
+    let router = router(&salesforce, json!({}));
+
+    assert_eq!(
+        update(&router, "key-1", "Acme").await,
+        (StatusCode::OK, false)
+    );
+    assert_eq!(
+        update(&router, "key-1", "Acme").await,
+        (StatusCode::OK, true)
+    );
+    assert_eq!(updates_sent(&salesforce), 1);
+
+    // A different key is a different write
+    assert_eq!(
+        update(&router, "key-2", "Acme").await,
+        (StatusCode::OK, false)
+    );
+    assert_eq!(updates_sent(&salesforce), 2);
+}
+
+#[tokio::test]
+async fn keys_reused_for_a_different_body_are_rejected() {
+    let salesforce = salesforce(Duration::ZERO).await;
+    let router = router(&salesforce, json!({}));
+
+    assert_eq!(
+        update(&router, "key-1", "Acme").await,
+        (StatusCode::OK, false)
+    );
+    assert_eq!(
+        update(&router, "key-1", "Globex").await,
+        (StatusCode::UNPROCESSABLE_ENTITY, false)
+    );
+    assert_eq!(updates_sent(&salesforce), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn repeats_of_a_write_in_progress_conflict_once_the_wait_runs_out() {
+    let salesforce = salesforce(Duration::from_millis(500)).await;
+    let router = router(&salesforce, json!({ "InFlightWaitMillis": 50 }));
+
+    let (first, second) = tokio::join!(
+        update(&router, "key-1", "Acme"),
+        update(&router, "key-1", "Acme"),
+    );
+
+    let mut statuses = [first.0, second.0];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+    assert_eq!(updates_sent(&salesforce), 1);
+
+    // Once the first request finishes, repeats get its response
+    assert_eq!(
+        update(&router, "key-1", "Acme").await,
+        (StatusCode::OK, true)
+    );
+}
+
+#[tokio::test]
+async fn expired_keys_are_not_replayed() {
+    let salesforce = salesforce(Duration::ZERO).await;
+    let router = router(&salesforce, json!({ "TtlSeconds": 0 }));
+
+    assert_eq!(
+        update(&router, "key-1", "Acme").await,
+        (StatusCode::OK, false)
+    );
+    assert_eq!(
+        update(&router, "key-1", "Globex").await,
+        (StatusCode::OK, false)
+    );
+    assert_eq!(updates_sent(&salesforce), 2);
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tempfile::NamedTempFile;
+
+use common::{
+    account_describe, api_key_router, as_caller, call, grant, json_response, policy_file,
+    MockSalesforce,
+};
+
+const CONTACTS_PATH: &str = "/objects/Account/001000000000001AAA/Contacts";
+
+const LOCATOR: &str = "01gD0000002HU6KIAW-2000";
+
+/// Accounts have their contacts as children.
+fn describe_with_contacts() -> Value {
+    let mut describe = account_describe();
+    describe["childRelationships"] = json!([{
+        "childSObject": "Contact",
+        "field": "AccountId",
+        "relationshipName": "Contacts",
+        "cascadeDelete": false,
+    }]);
+    describe
+}
+
+fn contact(id: &str) -> Value {
+    json!({ "attributes": { "type": "Contact" }, "Id": id })
+}
+
+async fn salesforce() -> MockSalesforce {
+    MockSalesforce::start(Arc::new(|method, uri, _, _| {
+        match (method.as_str(), uri.path()) {
+            ("GET", "/services/data/v59.0/sobjects/Account/describe") => {
+                json_response(200, describe_with_contacts())
+            }
+            ("GET", path) if path.ends_with("/Contacts") => json_response(
+                200,
+                json!({
+                    "totalSize": 2,
+                    "done": false,
+                    "records": [contact("003000000000001AAA")],
+                    "nextRecordsUrl": format!("/services/data/v59.0/query/{LOCATOR}"),
+                }),
+            ),
+            ("GET", path) if path == format!("/services/data/v59.0/query/{LOCATOR}") => {
+                json_response(
+                    200,
+                    json!({
+                        "totalSize": 2,
+                        "done": true,
+                        "records": [contact("003000000000002AAA")],
+                    }),
+                )
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }))
+    .await
+}
+
+/// Lets the underwriting UI read accounts and their contacts, while reporting may only read accounts.
+fn policy() -> NamedTempFile {
+    policy_file(json!({
+        "underwriting-ui": [grant(&["Account", "Contact"], &["Read"])],
+        "reporting": [grant(&["Account"], &["Read"])],
+    }))
+}
+
+/// Requests for related records, rather than describe metadata, that reached Salesforce.
+fn related_requests(salesforce: &MockSalesforce) -> Vec<String> {
+    salesforce
+        .api_requests()
+        .into_iter()
+        .filter(|request| !request.ends_with("/describe"))
+        .collect()
+}
+
+#[tokio::test]
+async fn related_objects_are_authorized_before_they_are_read() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let (status, _, _) = call(
+        &router,
+        "GET",
+        CONTACTS_PATH,
+        &as_caller("reporting-key"),
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(related_requests(&salesforce).is_empty());
+}
+
+#[tokio::test]
+async fn cursors_only_continue_the_relationship_they_came_from() {
+    let salesforce = salesforce().await;
+    let policy = policy();
+    let router = api_key_router(&salesforce, &policy, json!({}));
+
+    let (status, _, page) = call(&router, "GET", CONTACTS_PATH, &as_caller("ui-key"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    let cursor = page["nextCursor"].as_str().unwrap().to_string();
+    assert!(cursor.starts_with(LOCATOR) && cursor != LOCATOR);
+
+    let (status, _, page) = call(
+        &router,
+        "GET",
+        &format!("{CONTACTS_PATH}?cursor={cursor}"),
+        &as_caller("ui-key"),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["records"][0]["Id"], "003000000000002AAA");
+
+    // Neither the bare locator nor the cursor of another record's contacts is accepted
+    for path in [
+        format!("{CONTACTS_PATH}?cursor={LOCATOR}"),
+        format!("/objects/Account/001000000000002AAA/Contacts?cursor={cursor}"),
+    ] {
+        let (status, _, _) = call(&router, "GET", &path, &as_caller("ui-key"), "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}");
+    }
+
+    let queries = related_requests(&salesforce)
+        .iter()
+        .filter(|request| request.contains("/query/"))
+        .count();
+    assert_eq!(queries, 1);
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+
+use salesforce_api::router::ServiceRouter;
+
+use common::{account_describe, call, json_response, resolver, MockSalesforce};
+
+const UNDERWRITING: (&str, &str) = ("SF-Organization", "Underwriting");
+const ACCOUNT_DESCRIBE: &str = "/services/data/v59.0/sobjects/Account/describe";
+
+fn unavailable() -> axum::response::Response {
+    json_response(
+        503,
+        json!([{ "errorCode": "SERVICE_UNAVAILABLE", "message": "Try again later." }]),
+    )
+}
+
+fn underwriting(organization: Value) -> Value {
+    json!({ "Organizations": { "Underwriting": organization } })
+}
+
+/// A mock failing the Account describe `failures` times before answering it, recording when
+/// each attempt arrived.
+async fn failing_describe(failures: u32) -> (MockSalesforce, Arc<Mutex<Vec<Instant>>>) {
+    let attempts = Arc::new(Mutex::new(Vec::new()));
+    let received = attempts.clone();
+
+    let salesforce = MockSalesforce::start(Arc::new(move |_, uri, _, _| {
+        if uri.path() != ACCOUNT_DESCRIBE {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+
+        let mut received = received.lock().unwrap();
+        received.push(Instant::now());
+        if received.len() as u32 <= failures {
+            unavailable()
+        } else {
+            json_response(200, account_describe())
+        }
+    }))
+    .await;
+
+    (salesforce, attempts)
+}
+
+#[tokio::test]
+async fn idempotent_requests_are_retried_until_they_succeed() {
+    let (salesforce, attempts) = failing_describe(2).await;
+    let config = underwriting(json!({
+        "RetryPolicy": {
+            "MaxAttempts": 3,
+            "InitialBackoffMillis": 100,
+            "MaxBackoffMillis": 150,
+            "BackoffMultiplier": 2.0,
+        }
+    }));
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (status, _, body) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[UNDERWRITING],
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "Account");
+
+    let attempts = attempts.lock().unwrap();
+    assert_eq!(attempts.len(), 3);
+
+    // Backoff is jittered below a ceiling growing from the initial backoff and capped at the max
+    let slack = Duration::from_millis(250);
+    let waits: Vec<Duration> = attempts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    assert!(waits[0] <= Duration::from_millis(100) + slack, "{waits:?}");
+    assert!(waits[1] <= Duration::from_millis(150) + slack, "{waits:?}");
+}
+
+#[tokio::test]
+async fn retries_stop_after_the_maximum_attempts() {
+    let (salesforce, attempts) = failing_describe(5).await;
+    let config = underwriting(json!({
+        "RetryPolicy": { "MaxAttempts": 2, "InitialBackoffMillis": 10, "MaxBackoffMillis": 10 }
+    }));
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (status, _, _) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[UNDERWRITING],
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(attempts.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn non_idempotent_writes_are_not_retried() {
+    let updates = Arc::new(AtomicU32::new(0));
+    let received = updates.clone();
+    let salesforce = MockSalesforce::start(Arc::new(move |method, uri, _, _| {
+        match (method.as_str(), uri.path()) {
+            ("GET", ACCOUNT_DESCRIBE) => json_response(200, account_describe()),
+            ("PATCH", _) => {
+                received.fetch_add(1, Ordering::SeqCst);
+                unavailable()
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }))
+    .await;
+    let config = underwriting(json!({
+        "RetryPolicy": { "MaxAttempts": 3, "InitialBackoffMillis": 10, "MaxBackoffMillis": 10 }
+    }));
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (status, _, _) = call(
+        &router,
+        "PUT",
+        "/objects/Account/001000000000001AAA",
+        &[UNDERWRITING, ("Content-Type", "application/json")],
+        r#"{"Name":"Acme"}"#,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(updates.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn requests_waiting_to_retry_free_their_concurrency_slot() {
+    let first_attempt_failed = Arc::new(Notify::new());
+    let notify = first_attempt_failed.clone();
+    let account_describes = Arc::new(AtomicU32::new(0));
+
+    let salesforce = MockSalesforce::start(Arc::new(move |_, uri, _, _| match uri.path() {
+        ACCOUNT_DESCRIBE if account_describes.fetch_add(1, Ordering::SeqCst) == 0 => {
+            notify.notify_one();
+            unavailable()
+        }
+        ACCOUNT_DESCRIBE => json_response(200, account_describe()),
+        path if path.ends_with("/describe") => {
+            let mut describe = account_describe();
+            describe["name"] = json!("Contact");
+            json_response(200, describe)
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }))
+    .await;
+    let config = underwriting(json!({
+        "RetryPolicy": { "MaxAttempts": 2, "InitialBackoffMillis": 1000, "MaxBackoffMillis": 1000 },
+        "RateLimit": { "MaxConcurrentRequests": 1, "QueueTimeoutMillis": 50 },
+    }));
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, config)).unwrap();
+
+    let (retried, (status, _, _)) = tokio::join!(
+        call(
+            &router,
+            "GET",
+            "/objects/Account/describe",
+            &[UNDERWRITING],
+            ""
+        ),
+        async {
+            first_attempt_failed.notified().await;
+            call(
+                &router,
+                "GET",
+                "/objects/Contact/describe",
+                &[UNDERWRITING],
+                "",
+            )
+            .await
+        },
+    );
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(retried.0, StatusCode::OK);
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::json;
+
+use salesforce_api::router::ServiceRouter;
+
+use common::{account_describe, call, json_response, resolver, MockSalesforce};
+
+const UNDERWRITING: (&str, &str) = ("SF-Organization", "Underwriting");
+
+#[tokio::test]
+async fn token_endpoint_failures_are_reported_as_bad_gateway() {
+    let salesforce = MockSalesforce::start_with_token(
+        Arc::new(|_, _, _, _| {
+            json_response(
+                400,
+                json!({ "error": "invalid_grant", "error_description": "authentication failure" }),
+            )
+        }),
+        Arc::new(|_, _, _, _| json_response(200, account_describe())),
+    )
+    .await;
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, json!({}))).unwrap();
+
+    let (status, _, body) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[UNDERWRITING],
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["message"], "Authentication with Salesforce failed.");
+    assert!(!body.to_string().contains("invalid_grant"));
+    assert!(salesforce.api_requests().is_empty());
+}
+
+/// Issues a numbered access token for every token request, while the API only accepts tokens
+/// numbered from `first_accepted` onwards, answering older ones as an expired session.
+async fn salesforce_revoking_tokens(first_accepted: usize) -> MockSalesforce {
+    let issued = Arc::new(AtomicUsize::new(0));
+    let instance_url = Arc::new(std::sync::Mutex::new(String::new()));
+
+    let url = instance_url.clone();
+    let salesforce = MockSalesforce::start_with_token(
+        Arc::new(move |_, _, _, _| {
+            let token = issued.fetch_add(1, Ordering::SeqCst) + 1;
+            let instance_url = url.lock().unwrap().clone();
+            Json(json!({
+                "access_token": format!("token-{token}"),
+                "instance_url": instance_url,
+            }))
+            .into_response()
+        }),
+        Arc::new(move |_, _, headers, _| {
+            let token = headers["authorization"].to_str().unwrap();
+            let number: usize = token.trim_start_matches("Bearer token-").parse().unwrap();
+            if number < first_accepted {
+                return json_response(
+                    401,
+                    json!([{
+                        "message": "Session expired or invalid",
+                        "errorCode": "INVALID_SESSION_ID",
+                    }]),
+                );
+            }
+            json_response(200, account_describe())
+        }),
+    )
+    .await;
+    *instance_url.lock().unwrap() = salesforce.token_url.trim_end_matches("/token").to_string();
+
+    salesforce
+}
+
+fn token_requests(salesforce: &MockSalesforce) -> usize {
+    salesforce
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|request| request.contains(" /token"))
+        .count()
+}
+
+#[tokio::test]
+async fn rejected_sessions_are_renewed_and_the_request_retried() {
+    let salesforce = salesforce_revoking_tokens(2).await;
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, json!({}))).unwrap();
+
+    let (status, _, body) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[UNDERWRITING],
+        "",
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "Account");
+    assert_eq!(token_requests(&salesforce), 2);
+    assert_eq!(salesforce.api_requests().len(), 2);
+}
+
+#[tokio::test]
+async fn sessions_rejected_after_renewal_are_reported_as_bad_gateway() {
+    let salesforce = salesforce_revoking_tokens(usize::MAX).await;
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, json!({}))).unwrap();
+
+    let (status, _, _) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[UNDERWRITING],
+        "",
+    )
+    .await;
+
+    // Salesforce rejecting the service's own session says nothing about the caller's credentials
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(token_requests(&salesforce), 2);
+    assert_eq!(salesforce.api_requests().len(), 2);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use opentelemetry::trace::SpanKind;
+use opentelemetry::Value as AttributeValue;
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+use salesforce_api::router::ServiceRouter;
+use salesforce_api::telemetry;
+
+use common::{account_describe, call, json_response, resolver, MockSalesforce};
+
+fn attribute(span: &SpanData, key: &str) -> Option<AttributeValue> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == key)
+        .map(|attribute| attribute.value.clone())
+}
+
+#[tokio::test]
+async fn request_spans_are_exported_with_request_and_organization_attributes() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = telemetry::tracer_provider_with_exporter(exporter.clone());
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let salesforce = MockSalesforce::start(Arc::new(|_, _, _, _| {
+        json_response(200, account_describe())
+    }))
+    .await;
+    let router = ServiceRouter::new_router(resolver(&salesforce.token_url, json!({}))).unwrap();
+
+    let (status, headers, _) = call(
+        &router,
+        "GET",
+        "/objects/Account/describe",
+        &[
+            ("SF-Organization", "Underwriting"),
+            ("X-Request-Id", "telemetry-test-request"),
+        ],
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-request-id"], "telemetry-test-request");
+
+    // The simple exporter hands spans over on a background thread
+    for result in provider.force_flush() {
+        result.unwrap();
+    }
+    let spans = exporter.get_finished_spans().unwrap();
+    let request = spans
+        .iter()
+        .find(|span| span.span_kind == SpanKind::Server)
+        .expect("a server span for the request");
+
+    assert_eq!(request.name, "GET /objects/:name/describe");
+    assert_eq!(
+        attribute(request, "request_id"),
+        Some("telemetry-test-request".into())
+    );
+    assert_eq!(
+        attribute(request, "organization"),
+        Some("Underwriting".into())
+    );
+    assert_eq!(
+        attribute(request, "http.status_code").map(|status| status.as_str().into_owned()),
+        Some("200".to_string())
+    );
+
+    // Salesforce calls are exported as client spans within the request's trace
+    let salesforce_calls: Vec<&SpanData> = spans
+        .iter()
+        .filter(|span| span.span_kind == SpanKind::Client)
+        .collect();
+    assert!(!salesforce_calls.is_empty());
+    assert!(salesforce_calls.iter().all(|span| {
+        span.span_context.trace_id() == request.span_context.trace_id()
+            && attribute(span, "organization") == Some("Underwriting".into())
+    }));
+}